@@ -16,19 +16,18 @@ fn main() {
         // read_commandline does all the reading and tab completion
         match p.read_commandline() {
             Ok(line) => {
-                println!("");
-                match line.get(0).map(|s| s.as_str()) {
+                println!();
+                match line.first().map(|s| s.as_str()) {
                     Some("exit") => break,
-                    Some("print") | Some("echo") => {
-                        if line.len() > 1 {
-                            let output = line[1..]
-                                .iter()
-                                .map(|s| &**s)
-                                .collect::<Vec<&str>>()
-                                .join(" ");
-                            println!("{}", output);
-                        }
+                    Some("print") | Some("echo") if line.len() > 1 => {
+                        let output = line[1..]
+                            .iter()
+                            .map(|s| &**s)
+                            .collect::<Vec<&str>>()
+                            .join(" ");
+                        println!("{}", output);
                     }
+                    Some("print") | Some("echo") => {}
                     Some(cmd) => println!("Did not find '{}' command!", cmd),
                     None => {}
                 }
@@ -0,0 +1,97 @@
+//! Reading command lines in tokio based applications (`async` feature)
+
+use crate::error::Error;
+use crate::prompt::{no_check, Prompt};
+use crate::terminal::{keys, Key, RawMode, RawModeGuard};
+use std::io::{stdin, stdout, BufRead, IsTerminal, Write};
+use std::sync::OnceLock;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::Mutex;
+
+/// Keys read from stdin by a background thread, shared by the whole process
+type KeyReceiver = UnboundedReceiver<std::io::Result<Key>>;
+
+static KEYS: OnceLock<Mutex<KeyReceiver>> = OnceLock::new();
+
+/// Start a thread reading keys from stdin, since there is no asynchronous way to do that
+fn spawn_key_reader() -> Mutex<KeyReceiver> {
+    let (sender, receiver) = unbounded_channel();
+    std::thread::spawn(move || {
+        for key in keys() {
+            if sender.send(key).is_err() {
+                break;
+            }
+        }
+    });
+    Mutex::new(receiver)
+}
+
+impl Prompt {
+    /// Prompt for a single command line without blocking the async runtime.
+    ///
+    /// Editing, completion and history work exactly like in `read_commandline`.
+    /// Keys are read by a background thread, which is started on the first
+    /// call and keeps reading stdin afterwards. Therefore, don't mix this with
+    /// `read_commandline` in one process.
+    ///
+    /// # Cancellation safety
+    ///
+    /// The returned future may be dropped, e.g. when another branch of a
+    /// `tokio::select!` completes. Then, the terminal leaves raw mode, but no
+    /// key is lost: keys typed afterwards are read by the next call, which
+    /// goes on with the partially typed command line. In between, output may
+    /// be printed above it with `print_above`.
+    pub async fn read_commandline_async(&mut self) -> Result<Vec<String>, Error> {
+        if !stdin().is_terminal() {
+            let line = tokio::task::spawn_blocking(|| {
+                let mut line = String::new();
+                stdin().lock().read_line(&mut line).map(|_| line)
+            })
+            .await
+            .map_err(std::io::Error::other)??;
+            return self
+                .read_plain_line(line.as_bytes(), &mut stdout(), false, &no_check)
+                .map(|(_, words)| words);
+        }
+        let terminal = RawModeGuard::new()?;
+        let mut keys = KEYS.get_or_init(spawn_key_reader).lock().await;
+        self.read_keys_async(&mut keys, &mut stdout(), Some(terminal.raw_mode()))
+            .await
+    }
+
+    /// The read loop behind `read_commandline_async`
+    pub(crate) async fn read_keys_async<W: Write>(
+        &mut self,
+        keys: &mut KeyReceiver,
+        out: &mut W,
+        terminal: Option<&RawMode>,
+    ) -> Result<Vec<String>, Error> {
+        let mut state = self.begin_line(out)?;
+        loop {
+            // Kept while waiting, in case this future is dropped
+            self.interrupted = Some(state);
+            let key = match self.suggestion_delay.filter(|_| self.typing) {
+                Some(delay) => tokio::time::timeout(delay, keys.recv()).await.ok(),
+                None => Some(keys.recv().await),
+            };
+            state = self.interrupted.take().unwrap_or_default();
+            let Some(key) = key else {
+                // No key was pressed within `suggestion_delay`
+                self.show_suggestion(out, &state)?;
+                continue;
+            };
+            let Some(key) = key else {
+                break;
+            };
+            state.more_keys = !keys.is_empty();
+            let submitted = self.process_key(&mut state, key, out, terminal);
+            // Output following an error is not drawn over by the next read
+            self.status_shown &= submitted.is_ok();
+            let submitted = submitted?;
+            if submitted && self.submit_empty(&mut state, out)? && self.submit_valid(&state, out)? {
+                break;
+            }
+        }
+        Ok(self.finish_line(state).1)
+    }
+}
@@ -1,4 +1,6 @@
-use crate::split::split;
+use crate::split::{ends_with_whitespace, split};
+use std::cmp::Reverse;
+use std::collections::HashSet;
 
 /// A (sub)command may have arbitrary arguments, which the `Prompt`
 /// may describe to the user, when prompted for tab completion.
@@ -8,14 +10,60 @@ pub struct ArbitraryArgument {
     pub description: String,
 }
 
+impl ArbitraryArgument {
+    /// Create a new instance by specifying the argument's name and description
+    ///
+    /// ```
+    /// use shli::Command;
+    /// use shli::completion::{Argument, ArbitraryArgument};
+    ///
+    /// let file = ArbitraryArgument::new("FILE", "file to print");
+    /// let cmd = Command::new("cat").arg(Argument::ArbitraryArgument(file));
+    /// // Or, shorter:
+    /// let cmd = Command::new("cat").arg(("FILE", "file to print"));
+    /// ```
+    pub fn new(name: &str, description: &str) -> ArbitraryArgument {
+        ArbitraryArgument {
+            name: String::from(name),
+            description: String::from(description),
+        }
+    }
+
+    /// The argument as described in the parameter help, e.g. `FILE — file to print`
+    fn describe(&self) -> String {
+        if self.description.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} — {}", self.name, self.description)
+        }
+    }
+}
+
 /// Concrete possible argument
 ///
-/// A flag may have a number of mandatory arguments, which's
-/// number is `num_arguments`. Only after `arguments.len()`
-/// arguments after a flag, the next argument may be completed.
+/// A flag may have a number of mandatory arguments, given in `arguments`.
+/// Only after `arguments.len()` values following the flag, the next
+/// argument may be completed. Until then, completion describes the
+/// value expected next.
+///
+/// ```
+/// use shli::Command;
+/// use shli::completion::Flag;
+///
+/// // `--output` consumes one value, e.g. `cc --output main main.c`
+/// let cmd = Command::new("cc").arg(Flag::new("--output").value("FILE"));
+/// ```
+///
+/// A long flag may be given its value after `=`, like `--output=main`.
 pub struct Flag {
     pub name: String,
     pub arguments: Vec<ArbitraryArgument>,
+    /// Help text shown next to the flag in the completion list, if not empty
+    pub description: String,
+    /// Computes candidates for the values of the flag, which are described
+    /// instead if there is none. It receives the components typed before the
+    /// value being completed.
+    pub value_candidates: Option<DynamicCompletion>,
 }
 
 impl Flag {
@@ -23,14 +71,90 @@ impl Flag {
         Flag {
             name: String::from(flag),
             arguments: vec![],
+            description: String::new(),
+            value_candidates: None,
+        }
+    }
+
+    /// Set the help text shown next to the flag in the completion list
+    pub fn description(mut self, description: &str) -> Flag {
+        self.description = String::from(description);
+        self
+    }
+
+    /// Add a value this flag takes, named e.g. `FILE`
+    pub fn value(self, name: &str) -> Flag {
+        self.arg(ArbitraryArgument::new(name, ""))
+    }
+
+    /// Add a described value this flag takes
+    pub fn arg(mut self, argument: ArbitraryArgument) -> Flag {
+        self.arguments.push(argument);
+        self
+    }
+
+    /// Complete the values of this flag with `candidates`, see `value_candidates`
+    ///
+    /// ```
+    /// use shli::Command;
+    /// use shli::completion::{complete, CompletionResult, Flag};
+    ///
+    /// let color = Flag::new("--color")
+    ///     .value("WHEN")
+    ///     .complete_values(|_| vec!["always".to_string(), "never".to_string()]);
+    /// let commands = [Command::new("ls").arg(color)];
+    /// assert_eq!(
+    ///     complete("ls --color=n", &commands),
+    ///     CompletionResult::PossibilityList(vec!["--color=never".to_string()])
+    /// );
+    /// ```
+    pub fn complete_values(
+        mut self,
+        candidates: impl Fn(&[String]) -> Vec<String> + 'static,
+    ) -> Flag {
+        self.value_candidates = Some(Box::new(candidates));
+        self
+    }
+
+    /// The completions of the value of this flag starting with `prefix`,
+    /// `tokens` being the components before it
+    fn complete_value(
+        &self,
+        value: &ArbitraryArgument,
+        tokens: &[String],
+        prefix: &str,
+    ) -> CompletionResult {
+        match &self.value_candidates {
+            Some(candidates) => described_list(
+                candidates(tokens)
+                    .into_iter()
+                    .filter(|candidate| candidate.starts_with(prefix))
+                    .map(|candidate| (candidate, String::new()))
+                    .collect(),
+            ),
+            None => CompletionResult::Description(value.describe()),
         }
     }
+
+    /// The flag as shown in the completion list, with placeholders for its
+    /// arguments, e.g. `--output <FILE>`
+    pub fn display(&self) -> String {
+        let mut display = self.name.clone();
+        for argument in &self.arguments {
+            display.push_str(&format!(" <{}>", argument.name));
+        }
+        display
+    }
 }
 
+/// Computes completion candidates at runtime, see `Argument::Dynamic`
+pub type DynamicCompletion = Box<dyn Fn(&[String]) -> Vec<String>>;
+
 /// A (sub)command may have arguments which we divide into
-/// two categories:
+/// three categories:
 /// * A fixed set of flags/arguments
 /// * A free-text argument with a description
+/// * Arguments known only at runtime
 pub enum Argument {
     /// Concrete possible argument
     ///
@@ -40,6 +164,35 @@ pub enum Argument {
     /// may describe to the user, when prompted for tab completion.
     /// `name` and `description` are for informative purpose only.
     ArbitraryArgument(ArbitraryArgument),
+    /// Candidates computed when completing, e.g. branch names. The function
+    /// receives the components typed before the one being completed, starting
+    /// with the command name. Its candidates are completed just like flags.
+    Dynamic(DynamicCompletion),
+}
+
+impl Argument {
+    /// Create an `Argument::Dynamic` from `candidates`
+    ///
+    /// ```
+    /// use shli::Command;
+    /// use shli::completion::{complete, Argument, CompletionResult};
+    ///
+    /// let branches = Argument::dynamic(|_| vec!["main".to_string(), "dev".to_string()]);
+    /// let commands = [Command::new("git").subcommand(Command::new("checkout").arg(branches))];
+    /// assert_eq!(
+    ///     complete("git checkout m", &commands),
+    ///     CompletionResult::PossibilityList(vec!["main".to_string()])
+    /// );
+    /// ```
+    pub fn dynamic(candidates: impl Fn(&[String]) -> Vec<String> + 'static) -> Argument {
+        Argument::Dynamic(Box::new(candidates))
+    }
+}
+
+impl From<Flag> for Argument {
+    fn from(flag: Flag) -> Argument {
+        Argument::Flag(flag)
+    }
 }
 
 impl From<&str> for Argument {
@@ -48,6 +201,12 @@ impl From<&str> for Argument {
     }
 }
 
+impl From<(&str, &str)> for Argument {
+    fn from((name, description): (&str, &str)) -> Argument {
+        Argument::ArbitraryArgument(ArbitraryArgument::new(name, description))
+    }
+}
+
 /// Possible (sub)command displayed in tab completion.
 ///
 /// The arguments right from a (sub)command may be flags,
@@ -66,6 +225,13 @@ pub struct Command {
     pub args: Vec<Argument>,
     /// If calling completion after a subcommand, it may be completed
     pub subcommands: Vec<Command>,
+    /// Help text shown next to the command in the completion list, if not empty
+    pub description: String,
+    /// Other names of the command, which are resolved like its name but not
+    /// offered by completion
+    pub aliases: Vec<String>,
+    /// Resolve the command, but don't offer it in completion
+    pub hidden: bool,
 }
 
 impl Command {
@@ -75,9 +241,54 @@ impl Command {
             name: String::from(name),
             args: vec![],
             subcommands: vec![],
+            description: String::new(),
+            aliases: vec![],
+            hidden: false,
         }
     }
 
+    /// Don't offer the command in completion, e.g. because it is deprecated.
+    ///
+    /// Its arguments and subcommands are still completed once it is typed.
+    pub fn hidden(mut self) -> Command {
+        self.hidden = true;
+        self
+    }
+
+    /// Add another name for the command, e.g. `co` for `commit`
+    ///
+    /// ```
+    /// use shli::Command;
+    /// use shli::completion::{complete, CompletionResult};
+    ///
+    /// let commands = [Command::new("commit").alias("co").arg("--amend")];
+    /// assert_eq!(
+    ///     complete("co --a", &commands),
+    ///     CompletionResult::PossibilityList(vec!["--amend".to_string()])
+    /// );
+    /// ```
+    pub fn alias(mut self, name: &str) -> Command {
+        self.aliases.push(String::from(name));
+        self
+    }
+
+    /// Whether `name` is the name or an alias of this command
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+
+    /// Set the help text shown next to the command in the completion list
+    ///
+    /// ```
+    /// use shli::Command;
+    ///
+    /// let cmd = Command::new("exit").description("Leave the shell");
+    /// ```
+    pub fn description(mut self, description: &str) -> Command {
+        self.description = String::from(description);
+        self
+    }
+
     /// Add a subcommand to this command
     ///
     /// ```
@@ -102,63 +313,278 @@ impl Command {
         self.args.push(arg.into());
         self
     }
+
+    /// Walk down the subcommand tree along `tokens`
+    ///
+    /// `tokens` is a split command line starting with this command's name
+    /// (or an alias).
+    /// Tokens not naming a subcommand (e.g. flags) are skipped.
+    /// Returns the (sub)command active at the end of `tokens`,
+    /// or `None` if `tokens` doesn't start with this command.
+    ///
+    /// ```
+    /// use shli::Command;
+    ///
+    /// let cmd = Command::new("git").subcommand(Command::new("commit"));
+    /// let tokens = ["git", "-p", "commit"].map(String::from);
+    /// assert_eq!(cmd.resolve_path(&tokens).unwrap().name, "commit");
+    /// ```
+    pub fn resolve_path(&self, tokens: &[String]) -> Option<&Command> {
+        let (first, rest) = tokens.split_first()?;
+        if !self.is_named(first) {
+            return None;
+        }
+        let mut result = self;
+        for token in rest {
+            if let Some(cmd) = result.subcommands.iter().find(|cmd| cmd.is_named(token)) {
+                result = cmd;
+            }
+        }
+        Some(result)
+    }
+
+    /// The flag and its value expected next, if the end of `cmdline` is within
+    /// the values of a flag
+    fn pending_value(&self, cmdline: &[String]) -> Option<(&Flag, &ArbitraryArgument)> {
+        if cmdline.iter().any(|component| component == "--") {
+            // The flags ended, so there are no flag values either
+            return None;
+        }
+        for (given, component) in cmdline.iter().rev().enumerate() {
+            if let Some(flag) = self.flag(component) {
+                return flag.arguments.get(given).map(|value| (flag, value));
+            }
+        }
+        None
+    }
+
+    /// The flag of this command named `name`
+    fn flag(&self, name: &str) -> Option<&Flag> {
+        self.args.iter().find_map(|arg| match arg {
+            Argument::Flag(flag) if flag.name == name => Some(flag),
+            _ => None,
+        })
+    }
+
+    /// Returns the immediate possible arguments (flags, subcommands, …) of this command
+    ///
+    /// If the command takes arbitrary arguments, they are described instead.
+    /// If any flag or subcommand has a description, a `DescribedList` is returned.
+    /// A command without arguments and subcommands has nothing to complete
+    /// (`CompletionResult::None`).
+    /// Dynamic arguments receive no components, see `completions_after`.
+    pub fn completions(&self) -> CompletionResult {
+        self.completions_after(&[])
+    }
+
+    /// Returns the immediate possible arguments like `completions`, passing
+    /// `tokens`, the components typed so far, to dynamic arguments.
+    ///
+    /// After a standalone `--` in `tokens`, which ends the flags like in POSIX
+    /// command lines, flags are not offered any more.
+    pub fn completions_after(&self, tokens: &[String]) -> CompletionResult {
+        let flags_ended = tokens.iter().any(|token| token == "--");
+        let subcommands = command_names(&self.subcommands).into_iter();
+        if self.args.is_empty() {
+            // A leaf has nothing to complete, other commands only their subcommands
+            return if self.subcommands.is_empty() {
+                CompletionResult::None
+            } else {
+                described_list(subcommands.collect())
+            };
+        }
+        let mut list = vec![];
+        let mut descriptions = vec![];
+        for arg in &self.args {
+            match arg {
+                Argument::ArbitraryArgument(arg) => descriptions.push(arg.describe()),
+                Argument::Flag(_) if flags_ended => {}
+                Argument::Flag(flag) => list.push((flag.name.clone(), flag.description.clone())),
+                Argument::Dynamic(candidates) => list.extend(
+                    candidates(tokens)
+                        .into_iter()
+                        .map(|candidate| (candidate, String::new())),
+                ),
+            }
+        }
+        // If one argument is arbitrary, we can't return a fixed lists of arguments
+        if !descriptions.is_empty() {
+            return CompletionResult::Description(descriptions.join("; "));
+        }
+        list.extend(subcommands);
+        described_list(list)
+    }
 }
 
+/// The result of `complete`
+///
+/// Completing a single candidate appends a space, so the user can go on with
+/// the next argument, unless the candidate is partial (see `is_partial`).
+#[derive(Debug, PartialEq, Eq)]
 pub enum CompletionResult {
     None,
     Description(String),
     PossibilityList(Vec<String>),
+    /// Possible completions, each paired with its description
+    DescribedList(Vec<(String, String)>),
+}
+
+/// Whether `candidate` is only a part of the argument, which is to be completed further
+///
+/// This is the case for directories, written with a trailing `/`, e.g. `src/`.
+/// No space is appended after completing such a candidate.
+pub fn is_partial(candidate: &str) -> bool {
+    candidate.ends_with('/')
+}
+
+/// A `DescribedList` of `list`, or a `PossibilityList` if nothing in it is described.
+///
+/// Only the first of several candidates with the same name is kept.
+fn described_list(mut list: Vec<(String, String)>) -> CompletionResult {
+    let mut seen = HashSet::new();
+    list.retain(|(name, _)| seen.insert(name.clone()));
+    if list.iter().all(|(_, description)| description.is_empty()) {
+        CompletionResult::PossibilityList(list.into_iter().map(|(name, _)| name).collect())
+    } else {
+        CompletionResult::DescribedList(list)
+    }
 }
 
-fn command_names(commands: &[Command]) -> Vec<String> {
+/// The names of the `commands` offered by completion, with their descriptions
+fn command_names(commands: &[Command]) -> Vec<(String, String)> {
     let mut result = vec![];
-    for cmd in commands {
-        result.push(cmd.name.clone());
+    for cmd in commands.iter().filter(|cmd| !cmd.hidden) {
+        result.push((cmd.name.clone(), cmd.description.clone()));
     }
     result
 }
 
 /// Researches where in the command tree we are at the end of `cmdline`.
-fn active_command<'a>(cmdline: &[String], commands: &'a [Command]) -> Option<&'a Command> {
-    let mut result = None;
-    for component in cmdline {
-        for command in commands {
-            if *component == command.name {
-                result = Some(command)
+pub fn active_command<'a>(cmdline: &[String], commands: &'a [Command]) -> Option<&'a Command> {
+    commands.iter().find_map(|cmd| cmd.resolve_path(cmdline))
+}
+
+/// The text to show for `candidate` when listing the completions of `previous`.
+///
+/// Flags taking arguments are shown with placeholders (see `Flag::display`),
+/// while just the name returned by `complete` is inserted into the command line.
+pub fn display_text(previous: &str, candidate: &str, commands: &[Command]) -> String {
+    let mut components = split(previous);
+    if !ends_with_whitespace(previous) {
+        components.pop();
+    }
+    if let Some(cmd) = active_command(&components, commands) {
+        for arg in &cmd.args {
+            if let Argument::Flag(flag) = arg {
+                if flag.name == candidate {
+                    return flag.display();
+                }
             }
         }
     }
-    result
+    String::from(candidate)
 }
 
-/// Returns the possible arguments (flags, subvommands, …) of `cmd`as `CompletionResult`
-fn get_possible_completions(cmd: &Command) -> CompletionResult {
-    let mut list = vec![];
-    for arg in &cmd.args {
-        match arg {
-            // If one argument is arbitrary, we can't return a fixed lists of arguments
-            Argument::ArbitraryArgument(_) => {
-                return CompletionResult::Description(String::from("Various artists"))
-            }
-            Argument::Flag(flag) => list.push(flag.name.clone()),
+/// Generate completions for `previous`
+///
+/// `previous` is supposed to be the user string left from the cursor.
+/// The candidates are sorted by name and unique, see `complete_in_order`
+/// for keeping the order they are declared in.
+///
+/// ```
+/// use shli::Command;
+/// use shli::completion::{complete, CompletionResult};
+///
+/// let commands = [Command::new("pull"), Command::new("push"), Command::new("pull")];
+/// assert_eq!(
+///     complete("pu", &commands),
+///     CompletionResult::PossibilityList(vec!["pull".to_string(), "push".to_string()])
+/// );
+/// ```
+pub fn complete(previous: &str, commands: &[Command]) -> CompletionResult {
+    match complete_in_order(previous, commands) {
+        CompletionResult::PossibilityList(mut words) => {
+            words.sort();
+            CompletionResult::PossibilityList(words)
+        }
+        CompletionResult::DescribedList(mut list) => {
+            list.sort_by(|(name, _), (other, _)| name.cmp(other));
+            CompletionResult::DescribedList(list)
         }
+        result => result,
     }
-    for cmd in &cmd.subcommands {
-        list.push(cmd.name.clone())
+}
+
+/// Move the candidates of `result`, completing `previous`, that were used more
+/// often in `history` to the front.
+///
+/// A candidate counts as used by a history entry if the entry has it as
+/// command name when completing one, or else as a later component of an entry
+/// running the same command as `previous`. Candidates are ranked by their
+/// number of uses, then by how recent their last use is. Unused candidates
+/// follow in the order they had.
+///
+/// ```
+/// use shli::Command;
+/// use shli::completion::{complete, rank_by_history, CompletionResult};
+///
+/// let commands = [Command::new("git").subcommand(Command::new("pull")).subcommand(Command::new("push"))];
+/// let history = ["git push".to_string(), "ls".to_string(), "git push -f".to_string()];
+/// assert_eq!(
+///     rank_by_history(complete("git pu", &commands), "git pu", &history),
+///     CompletionResult::PossibilityList(vec!["push".to_string(), "pull".to_string()])
+/// );
+/// ```
+pub fn rank_by_history(
+    result: CompletionResult,
+    previous: &str,
+    history: &[String],
+) -> CompletionResult {
+    let components = split(previous);
+    let completing_command = components.len() + usize::from(ends_with_whitespace(previous)) <= 1;
+    let entries: Vec<Vec<String>> = history.iter().map(|entry| split(entry)).collect();
+    let rank = |candidate: &str| {
+        let mut uses = 0;
+        let mut last_use = 0;
+        for (index, words) in entries.iter().enumerate() {
+            let used = match (words.split_first(), components.first()) {
+                (Some((command, _)), _) if completing_command => command == candidate,
+                (Some((command, args)), Some(current)) => {
+                    command == current && args.iter().any(|arg| arg == candidate)
+                }
+                _ => false,
+            };
+            if used {
+                uses += 1;
+                last_use = index + 1;
+            }
+        }
+        Reverse((uses, last_use))
+    };
+    match result {
+        CompletionResult::PossibilityList(mut words) => {
+            words.sort_by_cached_key(|word| rank(word));
+            CompletionResult::PossibilityList(words)
+        }
+        CompletionResult::DescribedList(mut list) => {
+            list.sort_by_cached_key(|(name, _)| rank(name));
+            CompletionResult::DescribedList(list)
+        }
+        result => result,
     }
-    CompletionResult::PossibilityList(list)
 }
 
-/// Generate completions for `previous`
+/// Generate completions for `previous` like `complete`, but in the order the
+/// commands, arguments and subcommands are declared in.
 ///
-/// `previous` is supposed to be the user string left from the cursor
-pub fn complete(previous: &str, commands: &[Command]) -> CompletionResult {
+/// Of several candidates with the same name, only the first one is kept.
+pub fn complete_in_order(previous: &str, commands: &[Command]) -> CompletionResult {
     if previous.is_empty() {
         let possible_commands = command_names(commands);
         if possible_commands.is_empty() {
             CompletionResult::None
         } else {
-            CompletionResult::PossibilityList(possible_commands)
+            described_list(possible_commands)
         }
     } else {
         let mut components = split(previous);
@@ -181,11 +607,37 @@ pub fn complete(previous: &str, commands: &[Command]) -> CompletionResult {
         };
 
         let mut possibilities = if let Some(cmd) = active_command(&components, commands) {
-            if let CompletionResult::PossibilityList(possibilities) = get_possible_completions(cmd)
-            {
-                possibilities
-            } else {
-                return CompletionResult::Description(String::from("Various possible"));
+            if let Some((flag, value)) = cmd.pending_value(&components) {
+                return flag.complete_value(value, &components, &to_complete);
+            }
+            // The value of a long flag given as `--flag=value`
+            let assigned = to_complete
+                .split_once('=')
+                .filter(|(name, _)| name.starts_with("--"))
+                .filter(|_| !components.iter().any(|token| token == "--"))
+                .and_then(|(name, value)| Some((cmd.flag(name)?, value)));
+            if let Some((flag, prefix)) = assigned {
+                let Some(value) = flag.arguments.first() else {
+                    return CompletionResult::None;
+                };
+                components.push(flag.name.clone());
+                return match flag.complete_value(value, &components, prefix) {
+                    CompletionResult::PossibilityList(values) => CompletionResult::PossibilityList(
+                        values
+                            .into_iter()
+                            .map(|value| format!("{}={}", flag.name, value))
+                            .collect(),
+                    ),
+                    description => description,
+                };
+            }
+            match cmd.completions_after(&components) {
+                CompletionResult::PossibilityList(possibilities) => possibilities
+                    .into_iter()
+                    .map(|possibility| (possibility, String::new()))
+                    .collect(),
+                CompletionResult::DescribedList(possibilities) => possibilities,
+                description => return description,
             }
         } else if components.is_empty() {
             command_names(commands)
@@ -193,7 +645,55 @@ pub fn complete(previous: &str, commands: &[Command]) -> CompletionResult {
             vec![]
         };
 
-        possibilities.retain(|possibility| possibility.starts_with(&to_complete));
-        CompletionResult::PossibilityList(possibilities)
+        possibilities.retain(|(possibility, _)| possibility.starts_with(&to_complete));
+        described_list(possibilities)
     }
 }
+
+/// Build a `Vec<Command>` from a nested description, instead of the fluent API.
+///
+/// Every command is a name, optionally followed by braces containing its
+/// `flags: [...]` and then its subcommands, which are described the same way.
+/// A flag taking a value names it after `=`. The commands are the same as
+/// built by `Command::arg`, `Flag::value` and `Command::subcommand`.
+///
+/// ```
+/// use shli::commands;
+/// use shli::completion::{complete, CompletionResult};
+///
+/// let commands = commands! {
+///     "git" {
+///         flags: ["--version"]
+///         "commit" { flags: ["--amend", "--message" = "MSG"] }
+///         "push"
+///         "remote" {
+///             "add"
+///             "remove"
+///         }
+///     }
+///     "exit"
+/// };
+/// assert_eq!(
+///     complete("git remote ", &commands),
+///     CompletionResult::PossibilityList(vec!["add".to_string(), "remove".to_string()])
+/// );
+/// ```
+#[macro_export]
+macro_rules! commands {
+    (@command $command:expr; flags: [$($flag:literal $(= $value:literal)?),* $(,)?] $($rest:tt)*) => {
+        $crate::commands!(
+            @command $command$(.arg($crate::completion::Flag::new($flag)$(.value($value))?))*;
+            $($rest)*
+        )
+    };
+    (@command $command:expr; $($name:literal $({ $($body:tt)* })?)*) => {
+        $command$(.subcommand($crate::commands!(
+            @command $crate::completion::Command::new($name); $($($body)*)?
+        )))*
+    };
+    ($($name:literal $({ $($body:tt)* })?)*) => {
+        vec![$($crate::commands!(
+            @command $crate::completion::Command::new($name); $($($body)*)?
+        )),*]
+    };
+}
@@ -0,0 +1,182 @@
+//! Generation of completion scripts for shells like bash, and of help texts.
+//!
+//! Those complete the command line arguments of a program, whose
+//! subcommands are given as `Command` tree, just like the `Prompt` does.
+//! The same tree describes a command in its help text.
+
+use crate::completion::{Argument, Command};
+
+/// Quote `word` for a shell, so it is taken literally
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Collect the paths of `command` and its subcommands, each with its
+/// possible completions (flags and subcommands).
+fn collect(path: String, command: &Command, nodes: &mut Vec<(String, Vec<String>)>) {
+    let mut words = vec![];
+    for arg in &command.args {
+        if let Argument::Flag(flag) = arg {
+            words.push(flag.name.clone());
+        }
+    }
+    for subcommand in command.subcommands.iter().filter(|cmd| !cmd.hidden) {
+        words.push(subcommand.name.clone());
+    }
+    nodes.push((path.clone(), words));
+    for subcommand in &command.subcommands {
+        collect_named(&path, subcommand, nodes);
+    }
+}
+
+/// Collect `command` below `path` like `collect`, under its name and each alias
+fn collect_named(path: &str, command: &Command, nodes: &mut Vec<(String, Vec<String>)>) {
+    for name in std::iter::once(&command.name).chain(&command.aliases) {
+        collect(format!("{} {}", path, name), command, nodes);
+    }
+}
+
+/// Generate a bash completion script for the program `prog`, whose
+/// subcommands are `commands`.
+///
+/// Sourcing the script makes bash complete the flags and subcommands of
+/// `prog`. Where none of them fit, bash falls back to completing file names.
+///
+/// ```
+/// use shli::Command;
+/// use shli::generate::generate_bash_completion;
+///
+/// let script = generate_bash_completion("tool", &[Command::new("init")]);
+/// assert!(script.ends_with("complete -o default -F _tool 'tool'\n"));
+/// ```
+pub fn generate_bash_completion(prog: &str, commands: &[Command]) -> String {
+    let names = commands
+        .iter()
+        .filter(|command| !command.hidden)
+        .map(|command| command.name.clone())
+        .collect();
+    let mut nodes = vec![(String::from(prog), names)];
+    for command in commands {
+        collect_named(prog, command, &mut nodes);
+    }
+
+    let function = format!("_{}", prog.replace(|ch: char| !ch.is_alphanumeric(), "_"));
+    let mut script = format!("{}() {{\n", function);
+    script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str(&format!("    local path={}\n", shell_quote(prog)));
+    script.push_str("    local word\n");
+    script.push_str("    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n");
+    script.push_str("        case \"$path $word\" in\n");
+    let subpaths: Vec<String> = nodes[1..]
+        .iter()
+        .map(|(path, _)| shell_quote(path))
+        .collect();
+    if !subpaths.is_empty() {
+        script.push_str(&format!(
+            "            {}) path=\"$path $word\" ;;\n",
+            subpaths.join("|")
+        ));
+    }
+    script.push_str("        esac\n");
+    script.push_str("    done\n");
+    script.push_str("    case \"$path\" in\n");
+    for (path, words) in &nodes {
+        script.push_str(&format!(
+            "        {}) COMPREPLY=($(compgen -W {} -- \"$cur\")) ;;\n",
+            shell_quote(path),
+            shell_quote(&words.join(" "))
+        ));
+    }
+    script.push_str("    esac\n");
+    script.push_str("}\n");
+    script.push_str(&format!(
+        "complete -o default -F {} {}\n",
+        function,
+        shell_quote(prog)
+    ));
+    script
+}
+
+/// Generate a zsh completion script for the program `prog`, whose
+/// subcommands are `commands`.
+///
+/// This is the bash completion script, run by zsh's bash compatibility.
+pub fn generate_zsh_completion(prog: &str, commands: &[Command]) -> String {
+    format!(
+        "autoload -U +X bashcompinit && bashcompinit\n{}",
+        generate_bash_completion(prog, commands)
+    )
+}
+
+/// Format a help text for `command`, like the one printed by `--help`.
+///
+/// It consists of the description, a usage line and the sections `Commands`,
+/// `Arguments` and `Options`, listing the subcommands, arbitrary arguments
+/// and flags with their descriptions. Only the immediate subcommands are
+/// listed, without hidden ones. Dynamic arguments are left out.
+///
+/// ```
+/// use shli::Command;
+/// use shli::generate::format_help;
+///
+/// let cmd = Command::new("cat").description("Print files").arg(("FILE", "file to print"));
+/// assert_eq!(
+///     format_help(&cmd),
+///     "Print files\n\nUsage: cat <FILE>\n\nArguments:\n  <FILE>  file to print\n"
+/// );
+/// ```
+pub fn format_help(command: &Command) -> String {
+    let mut usage = command.name.clone();
+    let mut options = vec![];
+    let mut arguments = vec![];
+    for arg in &command.args {
+        match arg {
+            Argument::Flag(flag) => options.push((flag.display(), flag.description.as_str())),
+            Argument::ArbitraryArgument(argument) => {
+                let name = format!("<{}>", argument.name);
+                usage.push_str(&format!(" {}", name));
+                arguments.push((name, argument.description.as_str()));
+            }
+            Argument::Dynamic(_) => {}
+        }
+    }
+    let commands: Vec<(String, &str)> = command
+        .subcommands
+        .iter()
+        .filter(|subcommand| !subcommand.hidden)
+        .map(|subcommand| (subcommand.name.clone(), subcommand.description.as_str()))
+        .collect();
+    if !options.is_empty() {
+        usage.insert_str(command.name.len(), " [OPTIONS]");
+    }
+    if !commands.is_empty() {
+        usage.push_str(" [COMMAND]");
+    }
+
+    let sections = [
+        ("Commands", commands),
+        ("Arguments", arguments),
+        ("Options", options),
+    ];
+    // The descriptions of all sections start in the same column
+    let width = sections
+        .iter()
+        .flat_map(|(_, entries)| entries)
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut help = String::new();
+    if !command.description.is_empty() {
+        help.push_str(&format!("{}\n\n", command.description));
+    }
+    help.push_str(&format!("Usage: {}\n", usage));
+    for (title, entries) in sections.iter().filter(|(_, entries)| !entries.is_empty()) {
+        help.push_str(&format!("\n{}:\n", title));
+        for (name, description) in entries {
+            let line = format!("  {:width$}  {}", name, description, width = width);
+            help.push_str(line.trim_end());
+            help.push('\n');
+        }
+    }
+    help
+}
@@ -1,5 +1,9 @@
 //! shli provides a few raw building blocks for building your own shell-like CLI.
 //! It uses termion and should thus be compatible with all terminals termion supports.
+//! Alternatively, the `crossterm` feature (with default features disabled)
+//! uses crossterm, which also supports Windows.
+//! The `async` feature adds `Prompt::read_commandline_async` for tokio based applications.
+//! The `testing` feature adds `Prompt::run_keys` for testing with typed keys.
 //!
 //! An example:
 //! ```no_run
@@ -21,19 +25,18 @@
 //!         // read_commandline does all the reading and tab completion
 //!         match p.read_commandline() {
 //!             Ok(line) => {
-//!                 println!("");
-//!                 match line.get(0).map(|s| s.as_str()) {
+//!                 println!();
+//!                 match line.first().map(|s| s.as_str()) {
 //!                     Some("exit") => break,
-//!                     Some("print") | Some("echo") => {
-//!                         if line.len() > 1 {
-//!                             let output = line[1..]
-//!                                 .iter()
-//!                                 .map(|s| &**s)
-//!                                 .collect::<Vec<&str>>()
-//!                                 .join(" ");
-//!                             println!("{}", output);
-//!                         }
+//!                     Some("print") | Some("echo") if line.len() > 1 => {
+//!                         let output = line[1..]
+//!                             .iter()
+//!                             .map(|s| &**s)
+//!                             .collect::<Vec<&str>>()
+//!                             .join(" ");
+//!                         println!("{}", output);
 //!                     }
+//!                     Some("print") | Some("echo") => {}
 //!                     Some(cmd) => println!("Did not find '{}' command!", cmd),
 //!                     None => {}
 //!                 }
@@ -49,17 +52,30 @@
 //! }
 //! ```
 
-extern crate termion;
-
+#[cfg(feature = "async")]
+pub mod async_prompt;
 pub mod completion;
 pub mod error;
+pub mod generate;
 pub mod prompt;
+pub mod render;
 pub mod split;
+mod terminal;
+pub mod vi;
+pub mod width;
+pub mod word;
 
 pub use completion::Command;
 pub use error::Error;
-pub use prompt::Prompt;
-pub use split::split;
+pub use prompt::{
+    Action, BellStyle, CtrlDAction, EditMode, EmptyLineBehavior, InvalidLineBehavior, Prompt,
+    PromptBuilder, Validation,
+};
+pub use split::{
+    ends_with_whitespace, join, quote, split, split_iter, split_passthrough, split_with,
+    split_with_quotes, strip_comment, try_split, CharClass, EscapingState, ParseError, Tokenizer,
+};
+pub use terminal::{Key, RawModeGuard};
 
 #[cfg(test)]
 mod tests;
@@ -1,13 +1,428 @@
-use crate::completion::{complete, Command, CompletionResult};
+use crate::completion::{
+    complete, complete_in_order, display_text, is_partial, rank_by_history, Command,
+    CompletionResult,
+};
 use crate::error::Error;
-use crate::split::{ends_with_whitespace, split};
+use crate::render::{CompletionRenderer, DefaultRenderer};
+use crate::split::{quote, split, strip_comment, CharClass, EscapingState};
+use crate::terminal::Key::{self, Alt, Char, Ctrl};
+use crate::terminal::{
+    clear, cursor, key_within, keys, raise_interrupt, style, width, RawMode, RawModeGuard,
+};
+use crate::vi::{ViCommand, ViMode};
+use crate::width::visible_width;
+use crate::word::{next_word_boundary, next_word_start, prev_word_boundary};
+use std::borrow::Cow;
 use std::io::Write;
-use std::io::{stdin, stdout};
-use termion::cursor;
-use termion::event::Key::{self, Alt, Char, Ctrl};
-use termion::input::TermRead;
-use termion::raw::IntoRawMode;
-use termion::raw::RawTerminal;
+use std::io::{stdin, stdout, BufRead, IsTerminal};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, process};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Write `question` and read the answer from `keys`, see `Prompt::confirm`.
+pub(crate) fn read_confirmation<R, W>(
+    keys: R,
+    out: &mut W,
+    question: &str,
+    default: bool,
+) -> Result<bool, Error>
+where
+    R: Iterator<Item = std::io::Result<Key>>,
+    W: Write,
+{
+    write!(out, "{}", question)?;
+    out.flush()?;
+    for key in keys {
+        let answer = match key? {
+            Char('y' | 'Y') => true,
+            Char('n' | 'N') => false,
+            Char('\n') => default,
+            Ctrl('c') => return Err(Error::CtrlC),
+            Ctrl('d') => return Err(Error::CtrlD),
+            _ => continue,
+        };
+        write!(out, "{}\n\r", if answer { 'y' } else { 'n' })?;
+        out.flush()?;
+        return Ok(answer);
+    }
+    Err(Error::CtrlD)
+}
+
+/// Continue the numeric argument `count` with `key`, if `key` belongs to it.
+///
+/// A numeric argument starts with Alt and a digit. Following digits, with or
+/// without Alt, extend it.
+pub(crate) fn numeric_argument(count: Option<usize>, key: Key) -> Option<usize> {
+    let digit = match (count, key) {
+        (_, Alt(digit)) | (Some(_), Char(digit)) => digit.to_digit(10)?,
+        _ => return None,
+    };
+    Some(
+        count
+            .unwrap_or(0)
+            .saturating_mul(10)
+            .saturating_add(digit as usize),
+    )
+}
+
+/// Byte position in `line` where the component being typed at its end starts.
+///
+/// If `line` ends with a delimiter, a new component starts at `line.len()`.
+fn current_component_start(line: &str) -> usize {
+    let start = prev_word_boundary(line, line.len());
+    if start < line.len() && next_word_boundary(line, start) == line.len() {
+        start
+    } else {
+        line.len()
+    }
+}
+
+/// Remove the rest of the component the cursor is in from `right_line`,
+/// which starts right after `line`, so that a completion replaces all of it.
+fn drain_component_suffix(line: &str, right_line: &mut String) {
+    let mut state = EscapingState::process(line);
+    let suffix_len = match right_line.chars().next() {
+        Some(ch) if state.step_classify(ch) != CharClass::Delimiter => {
+            next_word_boundary(&format!("{}{}", line, right_line), line.len()) - line.len()
+        }
+        _ => 0,
+    };
+    right_line.drain(..suffix_len);
+}
+
+/// The character Ctrl+V inserts for `key`, if any.
+///
+/// Control keys are the ASCII control characters, like `'\x01'` for Ctrl+A.
+fn literal_char(key: Key) -> Option<char> {
+    match key {
+        Char(ch) => Some(ch),
+        Ctrl('?') | Key::Backspace => Some('\x7f'),
+        Ctrl(ch) => match ch.to_ascii_uppercase() {
+            ch @ '@'..='_' => Some(char::from(ch as u8 - 0x40)),
+            _ => None,
+        },
+        Key::Esc => Some('\x1b'),
+        Key::Null => Some('\0'),
+        _ => None,
+    }
+}
+
+/// How a character is shown on the terminal, with ASCII control characters
+/// in caret notation (`^A`)
+fn caret_notation(ch: char) -> String {
+    match ch {
+        '\x7f' => String::from("^?"),
+        ch if ch.is_ascii_control() => format!("^{}", char::from(ch as u8 + 0x40)),
+        ch => ch.to_string(),
+    }
+}
+
+/// `line` without leading and trailing whitespace, except for escaped whitespace
+fn trim_delimiters(line: &str) -> &str {
+    let mut state = EscapingState::new();
+    let mut end = 0;
+    for (pos, ch) in line.char_indices() {
+        if state.step_classify(ch) != CharClass::Delimiter {
+            end = pos + ch.len_utf8();
+        }
+    }
+    line[..end].trim_start()
+}
+
+/// The longest prefix all `words` start with
+fn common_prefix<'a>(mut words: impl Iterator<Item = &'a str>) -> &'a str {
+    let first = words.next().unwrap_or_default();
+    words.fold(first, |prefix, word| {
+        let len = prefix
+            .char_indices()
+            .zip(word.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((pos, ch), _)| pos + ch.len_utf8());
+        &prefix[..len]
+    })
+}
+
+/// Move the cursor to the byte position `pos` of the whole command line
+fn set_cursor(line: &mut String, right_line: &mut String, pos: usize) {
+    line.push_str(right_line);
+    *right_line = line.split_off(pos);
+}
+
+/// Apply `transform` to the current or next word right from the cursor
+/// and move the cursor behind it (Alt+U, Alt+L, Alt+C)
+pub(crate) fn transform_word(
+    line: &mut String,
+    right_line: &mut String,
+    transform: fn(&str) -> String,
+) {
+    let end = next_word_boundary(&format!("{}{}", line, right_line), line.len());
+    let rest = right_line.split_off(end - line.len());
+    line.push_str(&transform(right_line));
+    *right_line = rest;
+}
+
+/// Uppercase the first alphabetic character of `word` and lowercase the rest
+pub(crate) fn capitalize(word: &str) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut capitalized = false;
+    for ch in word.chars() {
+        if !capitalized && ch.is_alphabetic() {
+            result.extend(ch.to_uppercase());
+            capitalized = true;
+        } else {
+            result.extend(ch.to_lowercase());
+        }
+    }
+    result
+}
+
+/// The editors to try for Ctrl+X Ctrl+E: `$EDITOR`, then `vi`, then `nano`.
+fn editors() -> Vec<String> {
+    let mut editors: Vec<String> = env::var("EDITOR").into_iter().collect();
+    editors.push(String::from("vi"));
+    editors.push(String::from("nano"));
+    editors
+}
+
+/// Create a file for `edit_externally` in the temporary directory, under a
+/// name no other file has.
+///
+/// The file is created exclusively, so an existing file or symbolic link of
+/// that name is never written to, and only the user may read it.
+fn create_temp_file() -> std::io::Result<(PathBuf, fs::File)> {
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        let count = CREATED.fetch_add(1, Ordering::Relaxed);
+        let name = format!("shli-{}-{}-{:08x}.txt", process::id(), count, nanos);
+        let path = env::temp_dir().join(name);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Let the user edit `text` in the first of `editors` that can be started.
+///
+/// An editor entry may contain arguments (e.g. `code --wait`).
+/// Returns the edited text without trailing newlines, or `None` if no editor
+/// could be found or the editor exited with a non-zero status.
+pub(crate) fn edit_externally(text: &str, editors: &[String]) -> std::io::Result<Option<String>> {
+    let (path, mut file) = create_temp_file()?;
+    let written = file.write_all(text.as_bytes());
+    drop(file);
+    let result = written.and_then(|()| run_editor(&path, editors));
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// Run the first of `editors` that can be started on the file at `path`,
+/// see `edit_externally`
+fn run_editor(path: &Path, editors: &[String]) -> std::io::Result<Option<String>> {
+    for editor in editors {
+        let mut args = split(editor);
+        if args.is_empty() {
+            continue;
+        }
+        let program = args.remove(0);
+        match process::Command::new(program).args(args).arg(path).status() {
+            Ok(status) if status.success() => {
+                return fs::read_to_string(path)
+                    .map(|edited| Some(edited.trim_end_matches(['\n', '\r']).to_string()));
+            }
+            Ok(_) => return Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// The state of a command line while it is being edited
+#[derive(Default)]
+pub(crate) struct LineState {
+    /// The text left from the cursor
+    pub line: String,
+    /// The text right from the cursor
+    pub right_line: String,
+    /// How many entries the user went back in the history
+    pub history_offset: usize,
+    /// The command line as it was before browsing the history
+    pub draft: String,
+    /// Ctrl+X was pressed, waiting for the next key of the sequence
+    pub ctrl_x: bool,
+    /// Ctrl+V was pressed, the next key is inserted literally
+    pub quoted_insert: bool,
+    pub vi: ViMode,
+    /// Numeric argument for the next key
+    pub pending_count: Option<usize>,
+    pub completion: CompletionState,
+    /// More keys are available right away, e.g. because text was pasted
+    pub more_keys: bool,
+    /// Characters were inserted without drawing them, because `more_keys` was set
+    pub stale: bool,
+    /// An empty command line was submitted, repeating the last history entry
+    pub repeated: bool,
+}
+
+/// How far completing the command line got with the previous key.
+///
+/// Every key except the completion key resets it to `Idle`, so cursor
+/// movements, editing keys, Enter and Ctrl+C end a pending completion.
+/// A key answering `Querying` resets it as well, whatever the answer.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) enum CompletionState {
+    #[default]
+    Idle,
+    /// The previous key was the completion key, so pressing it again lists
+    /// the completions with `Prompt::complete_prefix_first`
+    Completed,
+    /// Asked whether to list these completions, answered by the next key
+    Querying(Vec<(String, String)>),
+}
+
+/// Key bindings used by `read_commandline`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditMode {
+    /// Readline's default, emacs-like bindings
+    Emacs,
+    /// Vi bindings with insert and normal mode, see `ViMode`
+    Vi,
+}
+
+/// What submitting an empty command line does, see `Prompt::empty_line`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyLineBehavior {
+    /// Return no components
+    Return,
+    /// Return the most recent history entry again, if there is one, without
+    /// adding it to the history another time
+    RepeatLast,
+    /// Print the prompt again and go on reading
+    Ignore,
+}
+
+/// What submitting a command line the validator finds invalid does,
+/// see `Prompt::invalid_line`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidLineBehavior {
+    /// Show the message below the command line and go on editing it
+    Reject,
+    /// Show the message below the command line and accept it anyway
+    Warn,
+}
+
+/// The verdict of a validator on the command line, see `Prompt::with_validator`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Validation {
+    Valid,
+    /// The command line is invalid, for the reason given
+    Invalid(String),
+}
+
+/// What Ctrl+D does if the command line isn't empty, see `Prompt::ctrl_d_action`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtrlDAction {
+    /// Nothing
+    Ignore,
+    /// Delete the character right from the cursor, like readline's `delete-char`
+    DeleteChar,
+}
+
+/// What happens when tab completion finds nothing, see `Prompt::bell_style`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BellStyle {
+    /// Nothing, the command line just stays as it is
+    None,
+    /// Ring the terminal bell (`\x07`)
+    Audible,
+    /// Write the given escape sequence, e.g. one flashing the screen
+    Visible(String),
+}
+
+/// What to do on a special key, returned by the handler given to
+/// `Prompt::with_special_key_handler`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Insert the text at the cursor
+    Insert(String),
+    /// Replace the whole command line with the text
+    Replace(String),
+    /// Print the text below the command line, which is drawn again after it
+    Print(String),
+    /// Go to the oldest history entry
+    HistoryStart,
+}
+
+/// Colors the prompt while the command line is invalid, see `Prompt::with_validator`
+const INVALID_COLOR: &str = "\x1b[31m";
+
+/// Decorates the command line with ANSI escape sequences, see `Prompt::with_highlighter`
+type Highlighter = Box<dyn Fn(&str) -> String>;
+
+/// Maps every typed character to the text shown for it, see `Prompt::with_echo`
+type Echo = Box<dyn Fn(char) -> Option<String>>;
+
+/// Checks the command line after every edit, see `Prompt::with_validator`
+type Validator = Box<dyn Fn(&str) -> Validation>;
+
+/// Receives every accepted command line, see `Prompt::with_line_callback`
+type LineCallback = Box<dyn FnMut(&str)>;
+
+/// Transforms the components of every accepted command line,
+/// see `Prompt::with_postprocessor`
+type Postprocessor = Box<dyn Fn(Vec<String>) -> Vec<String>>;
+
+/// Decides what Insert, PageUp, PageDown and the function keys do,
+/// see `Prompt::with_special_key_handler`
+type SpecialKeyHandler = Box<dyn FnMut(Key) -> Option<Action>>;
+
+/// Validates the components of a submitted command line, returning an error
+/// message to show if it is not accepted
+pub(crate) type Check<'a> = &'a dyn Fn(&[String]) -> Result<(), String>;
+
+/// Accepts every command line
+pub(crate) fn no_check(_: &[String]) -> Result<(), String> {
+    Ok(())
+}
+
+/// Accepts command lines of `min` to `max` components, see `Prompt::read_args`
+pub(crate) fn check_arg_count(
+    words: &[String],
+    min: usize,
+    max: Option<usize>,
+) -> Result<(), String> {
+    let count = words.len();
+    match max {
+        Some(max) if count > max || count < min => {
+            if min == max {
+                Err(format!("Expected {} arguments, got {}", min, count))
+            } else {
+                Err(format!(
+                    "Expected {} to {} arguments, got {}",
+                    min, max, count
+                ))
+            }
+        }
+        None if count < min => Err(format!(
+            "Expected at least {} arguments, got {}",
+            min, count
+        )),
+        _ => Ok(()),
+    }
+}
 
 /// Config struct for building command line interfaces.
 /// An example:
@@ -28,6 +443,111 @@ pub struct Prompt {
     pub prompt_text: String,
     pub history: Vec<String>,
     pub commands: Vec<Command>,
+    /// Text removed by the most recent kill command (Ctrl+K, Ctrl+U, Ctrl+W),
+    /// which is inserted again by Ctrl+Y.
+    pub kill_buffer: String,
+    /// Show the most recent history entry starting with the typed text as
+    /// dimmed suggestion right from the cursor. Right or End accept it.
+    pub autosuggest: bool,
+    /// Show the suggestion only once no key was pressed for this long, instead
+    /// of after every key, and let Right and End accept only a shown suggestion.
+    /// `None` by default.
+    ///
+    /// `read_from` takes the keys of its iterator as typed without a pause, so
+    /// it never shows the suggestion. `read_commandline_async` waits on a tokio
+    /// timer, which needs the time driver of the runtime to be enabled.
+    pub suggestion_delay: Option<Duration>,
+    pub edit_mode: EditMode,
+    /// Signal that tab completion found nothing. Defaults to `BellStyle::None`.
+    pub bell_style: BellStyle,
+    /// Record accepted command lines in `history` and let the user browse it.
+    /// On by default. When off, nothing is added to `history`, Up and Down do
+    /// nothing and neither suggestions nor repetitions are taken from it.
+    pub history_enabled: bool,
+    /// Remove leading and trailing (unescaped) whitespace from accepted command lines,
+    /// before adding them to the history. Off by default.
+    pub trim_on_submit: bool,
+    /// The most characters a command line may have, including the lines before
+    /// a line break. Further typed characters are ignored, ringing the bell.
+    /// `None`, the default, doesn't limit it.
+    pub max_line_len: Option<usize>,
+    /// What submitting an empty command line does. Defaults to
+    /// `EmptyLineBehavior::Return`.
+    pub empty_line: EmptyLineBehavior,
+    /// What submitting a command line the validator finds invalid does.
+    /// Defaults to `InvalidLineBehavior::Reject`.
+    pub invalid_line: InvalidLineBehavior,
+    /// Drop everything from this unquoted marker at the start of a component
+    /// on, usually `#`, from the components returned. The history keeps the
+    /// comment. `None` by default.
+    pub comment_marker: Option<String>,
+    /// What Ctrl+D does within a command line. Only on an empty command line,
+    /// it ends reading with `Error::CtrlD`. Defaults to `CtrlDAction::DeleteChar`.
+    pub ctrl_d_action: CtrlDAction,
+    /// Ask the user before listing more completions than this, like
+    /// `Display all 120 possibilities? (y/n)`. `None` lists them all.
+    pub completion_query_items: Option<usize>,
+    /// Like bash, insert the longest common prefix of several completions
+    /// first, and only list them if the completion key is pressed again without
+    /// getting any further. By default, they are listed right away.
+    pub complete_prefix_first: bool,
+    /// Offer completions sorted by name, which is the default. When off, they
+    /// are offered in the order they are declared in, see `complete_in_order`.
+    pub sort_completions: bool,
+    /// Offer the completions used most often in the history first, see
+    /// `rank_by_history`. Off by default.
+    pub rank_completions: bool,
+    /// Catch a panic while computing completions, e.g. in an `Argument::dynamic`
+    /// closure. The bell (`\x07`) is rung regardless of `bell_style`, since the
+    /// panic is an error rather than an empty result, and editing goes on.
+    /// On by default, turn it off to let the panic unwind out of the read.
+    pub catch_completion_panics: bool,
+    /// The prompt text shown on the following lines of a command line continued
+    /// by a trailing backslash
+    pub continuation_prompt: String,
+    /// On Ctrl+C, restore the terminal and send SIGINT to the own process, so
+    /// that its signal handler (or the default termination) runs.
+    ///
+    /// In raw mode, the terminal doesn't generate signals (ISIG is off), so
+    /// Ctrl+C is read as a key and results in `Error::CtrlC` by default.
+    /// If the signal is handled without terminating, `Error::CtrlC` is
+    /// returned afterwards anyway. This only applies to the reading methods
+    /// using the terminal, like `read_commandline`, and only on Unix.
+    pub raise_sigint: bool,
+    highlighter: Option<Highlighter>,
+    echo: Option<Echo>,
+    validator: Option<Validator>,
+    line_callback: Option<LineCallback>,
+    postprocessor: Option<Postprocessor>,
+    special_key_handler: Option<SpecialKeyHandler>,
+    completion_key: Key,
+    renderer: Box<dyn CompletionRenderer>,
+    /// The lines before the current one of a continued command line
+    continued: Option<String>,
+    /// The number of terminal lines above the current one showing `continued`
+    continued_rows: usize,
+    submit_key: Key,
+    newline_key: Key,
+    /// Index of the history entry to start the next command line with,
+    /// set by Ctrl+O and cleared once used
+    next_history: Option<usize>,
+    last_status: Option<i32>,
+    /// The command line discarded by the most recent Ctrl+C
+    cancelled: Option<String>,
+    /// The text of the status line above the command line, if enabled
+    status: Option<String>,
+    /// A read printed the status line and the command line below, and didn't
+    /// finish, so the cursor is still within the command line
+    pub(crate) status_shown: bool,
+    /// The command line of a read waiting for a key, kept if the read is
+    /// cancelled, so that the next read goes on with it
+    pub(crate) interrupted: Option<LineState>,
+    /// Visible width of `prompt_text`, updated by `set_prompt` and before reading
+    prompt_width: usize,
+    /// Keys were pressed within `suggestion_delay`, so the suggestion isn't drawn
+    pub(crate) typing: bool,
+    /// The suggestion is on the screen, so that it may be accepted with `suggestion_delay`
+    suggestion_shown: bool,
 }
 
 impl Prompt {
@@ -37,231 +557,2063 @@ impl Prompt {
     /// `commands` is the list of available commands used by tab completion.
     pub fn new(prompt_text: String, commands: Vec<Command>) -> Prompt {
         Prompt {
+            prompt_width: visible_width(&prompt_text),
             prompt_text,
             history: vec![],
             commands,
+            kill_buffer: String::new(),
+            autosuggest: false,
+            suggestion_delay: None,
+            edit_mode: EditMode::Emacs,
+            bell_style: BellStyle::None,
+            history_enabled: true,
+            trim_on_submit: false,
+            max_line_len: None,
+            empty_line: EmptyLineBehavior::Return,
+            invalid_line: InvalidLineBehavior::Reject,
+            comment_marker: None,
+            ctrl_d_action: CtrlDAction::DeleteChar,
+            completion_query_items: None,
+            complete_prefix_first: false,
+            sort_completions: true,
+            rank_completions: false,
+            catch_completion_panics: true,
+            continuation_prompt: String::from("> "),
+            raise_sigint: false,
+            highlighter: None,
+            echo: None,
+            validator: None,
+            line_callback: None,
+            postprocessor: None,
+            special_key_handler: None,
+            completion_key: Char('\t'),
+            renderer: Box::new(DefaultRenderer),
+            continued: None,
+            continued_rows: 0,
+            submit_key: Char('\n'),
+            newline_key: Alt('\r'),
+            next_history: None,
+            last_status: None,
+            cancelled: None,
+            status: None,
+            status_shown: false,
+            interrupted: None,
+            typing: false,
+            suggestion_shown: false,
         }
     }
 
-    /// Reprint the command line in the current terminal line.
-    /// `right_line` refers to the command part supposed to be right from the cursor.
-    fn reprint(
-        &self,
-        stdout: &mut RawTerminal<std::io::StdoutLock>,
-        line: &str,
-        right_line: &str,
-    ) -> std::io::Result<()> {
-        write!(stdout, "\r{}{}{}", &self.prompt_text, line, right_line)?;
-        if !right_line.is_empty() {
-            write!(stdout, "{}", cursor::Left(right_line.len() as u16))?;
+    /// Configure a Prompt option by option, instead of using `new`.
+    ///
+    /// Options that aren't set keep the defaults of `new`.
+    ///
+    /// ```
+    /// use shli::{Command, EditMode, Prompt};
+    ///
+    /// let p = Prompt::builder()
+    ///     .prompt_text("$ ")
+    ///     .command(Command::new("exit"))
+    ///     .edit_mode(EditMode::Vi)
+    ///     .autosuggest(true)
+    ///     .build();
+    /// assert_eq!(p.prompt_text, "$ ");
+    /// ```
+    pub fn builder() -> PromptBuilder {
+        PromptBuilder {
+            prompt: Prompt::new(String::new(), vec![]),
         }
-        stdout.flush()?;
-        Ok(())
     }
 
-    fn completion(
-        &self,
-        stdout: &mut RawTerminal<std::io::StdoutLock>,
-        line: &mut String,
-        right_line: &str,
+    /// Colorize the command line while the user types.
+    ///
+    /// `highlighter` receives the whole command line and returns it decorated
+    /// with ANSI escape sequences, which is then printed instead.
+    /// It must not change the visible characters, only add escape sequences,
+    /// because the cursor position is computed from the undecorated text.
+    ///
+    /// ```
+    /// use shli::Prompt;
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![]).with_highlighter(|line| {
+    ///     line.replace("print", "\x1b[32mprint\x1b[0m")
+    /// });
+    /// ```
+    pub fn with_highlighter(mut self, highlighter: impl Fn(&str) -> String + 'static) -> Prompt {
+        self.highlighter = Some(Box::new(highlighter));
+        self
+    }
+
+    /// Check the command line while the user types.
+    ///
+    /// `validator` receives the whole command line after every edit. While it
+    /// is invalid, the prompt is shown in red. Submitting an invalid command
+    /// line shows the message below it and, depending on `invalid_line`,
+    /// rejects or accepts it.
+    ///
+    /// ```
+    /// use shli::{Prompt, Validation};
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![]).with_validator(|line| {
+    ///     if line.trim().is_empty() {
+    ///         Validation::Invalid("Type a command".to_string())
+    ///     } else {
+    ///         Validation::Valid
+    ///     }
+    /// });
+    /// ```
+    pub fn with_validator(mut self, validator: impl Fn(&str) -> Validation + 'static) -> Prompt {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Show every typed character as the text returned by `echo` instead.
+    ///
+    /// `None` shows nothing for the character. Only the display changes, the
+    /// command line returned keeps the typed characters. The highlighter and
+    /// the history suggestion aren't shown while an echo is set.
+    ///
+    /// ```
+    /// use shli::Prompt;
+    ///
+    /// // Mask a password, which must not end up in the history either
+    /// let mut p = Prompt::new("Password: ".to_string(), vec![]).with_echo(|_| Some("*".to_string()));
+    /// p.history_enabled = false;
+    /// ```
+    pub fn with_echo(mut self, echo: impl Fn(char) -> Option<String> + 'static) -> Prompt {
+        self.echo = Some(Box::new(echo));
+        self
+    }
+
+    /// Use `key` instead of Tab to ask for completion.
+    ///
+    /// Tab then inserts a tabulator like any other character.
+    ///
+    /// ```
+    /// use shli::{Key, Prompt};
+    ///
+    /// // Ctrl+Space is read as `Key::Null`
+    /// let p = Prompt::new("> ".to_string(), vec![]).with_completion_key(Key::Null);
+    /// ```
+    pub fn with_completion_key(mut self, key: Key) -> Prompt {
+        self.completion_key = key;
+        self
+    }
+
+    /// Submit the command line with `key` instead of Enter.
+    ///
+    /// Enter then inserts a line break, just like the newline key.
+    pub fn with_submit_key(mut self, key: Key) -> Prompt {
+        self.submit_key = key;
+        self
+    }
+
+    /// Insert a line break into the command line with `key` instead of Alt+Enter.
+    ///
+    /// ```
+    /// use shli::{Key, Prompt};
+    ///
+    /// // Enter inserts line breaks, Ctrl+S submits
+    /// let p = Prompt::new("sql> ".to_string(), vec![]).with_submit_key(Key::Ctrl('s'));
+    /// ```
+    pub fn with_newline_key(mut self, key: Key) -> Prompt {
+        self.newline_key = key;
+        self
+    }
+
+    /// Use `renderer` to show the completions, instead of `DefaultRenderer`.
+    ///
+    /// ```
+    /// use shli::Prompt;
+    /// use shli::render::ColumnRenderer;
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![]).with_completion_renderer(ColumnRenderer);
+    /// ```
+    pub fn with_completion_renderer(
+        mut self,
+        renderer: impl CompletionRenderer + 'static,
+    ) -> Prompt {
+        self.renderer = Box::new(renderer);
+        self
+    }
+
+    /// Reserve the line above the command line for a status, e.g. a spinner,
+    /// see `set_status`.
+    ///
+    /// Every read prints the status line first, followed by the prompt in the
+    /// next line, so there is always room above the command line.
+    ///
+    /// ```
+    /// use shli::Prompt;
+    ///
+    /// let mut p = Prompt::new("> ".to_string(), vec![]).with_status_line();
+    /// p.set_status("Downloading… 42%").unwrap();
+    /// ```
+    pub fn with_status_line(mut self) -> Prompt {
+        self.status = Some(String::new());
+        self
+    }
+
+    /// Change the text of the status line reserved by `with_status_line`.
+    ///
+    /// It is shown by the next read. If a read was interrupted, like by
+    /// cancelling `read_commandline_async`, the status line is redrawn right
+    /// away, keeping the command line below as it is. This saves and restores
+    /// the cursor position (`ESC [ s`, `ESC [ u`) and moves it up, which
+    /// common terminals support. Completions listed below the status line
+    /// are not taken into account, though.
+    pub fn set_status(&mut self, text: &str) -> std::io::Result<()> {
+        self.set_status_on(&mut stdout().lock(), text)
+    }
+
+    /// Change the text of the status line like `set_status`, writing to `out`
+    pub(crate) fn set_status_on<W: Write>(
+        &mut self,
+        out: &mut W,
+        text: &str,
     ) -> std::io::Result<()> {
-        match complete(line, &self.commands) {
-            CompletionResult::None => {}
-            CompletionResult::Description(description) => {
-                write!(stdout, "\n\r Parameter help: {}\n\r> {}", description, line)?;
-            }
-            CompletionResult::PossibilityList(possible_words) => {
-                if possible_words.len() == 1 {
-                    // First, replace the last word
-                    let mut words = split(line);
-                    if !ends_with_whitespace(line) {
-                        words.pop();
-                    }
-                    words.push(possible_words[0].clone());
-                    // Now build up the cmdline again
-                    *line = String::new();
-                    for word in words {
-                        line.push_str(&word);
-                        line.push(' ');
-                    }
-                    // Now display the new cmdline
-                    self.reprint(stdout, line, right_line)?;
-                } else if !possible_words.is_empty() {
-                    // Display the possibilities
-                    write!(
-                        stdout,
-                        "\n\r Completions: {:?}\n\r> {}",
-                        possible_words, line
-                    )?;
-                    stdout.flush()?;
-                }
-            }
+        let Some(status) = &mut self.status else {
+            return Ok(());
         };
+        *status = String::from(text);
+        if self.status_shown {
+            let rows = self.continued_rows as u16 + 1;
+            write!(out, "{}{}\r{}", cursor::Save, cursor::Up(rows), text)?;
+            write!(out, "{}{}", clear::UntilNewline, cursor::Restore)?;
+            out.flush()?;
+        }
         Ok(())
     }
 
-    /// Convenience function to replace the current edit buffer while prompting
-    fn replace_cmdline(
-        &self,
-        stdout: &mut termion::raw::RawTerminal<std::io::StdoutLock>,
-        new_cmd_line: &str,
-        line: &mut String,
-        right_line: &mut String,
-    ) -> Result<(), Error> {
-        let chars_to_wipe = self.prompt_text.len() + line.len() + right_line.len();
-        *line = String::from(new_cmd_line);
-        write!(stdout, "\r")?;
-        for _ in 0..chars_to_wipe {
-            write!(stdout, " ")?;
-        }
-        *right_line = String::new();
-        self.reprint(stdout, line, right_line)?;
-        Ok(())
+    /// Let `handler` decide what Insert, PageUp, PageDown and the function keys
+    /// (`Key::F`) do while reading a command line.
+    ///
+    /// These keys do nothing by default, and neither if `handler` returns `None`.
+    ///
+    /// ```
+    /// use shli::{Action, Key, Prompt};
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![]).with_special_key_handler(|key| match key {
+    ///     Key::F(1) => Some(Action::Print("Type `help` for a list of commands".to_string())),
+    ///     Key::PageUp => Some(Action::HistoryStart),
+    ///     _ => None,
+    /// });
+    /// ```
+    pub fn with_special_key_handler(
+        mut self,
+        handler: impl FnMut(Key) -> Option<Action> + 'static,
+    ) -> Prompt {
+        self.special_key_handler = Some(Box::new(handler));
+        self
     }
 
-    /// Prompt for a single command line.
+    /// Call `callback` with every accepted command line, before it is split.
     ///
-    /// This function reads and returns a command line.
-    /// Line editing with backspace and ALT+backspace is supported.
+    /// The callback fires just before `read_commandline` (or any other reading
+    /// method) returns successfully, with the whole command line, including
+    /// text recalled from the history. Just like the history, it doesn't fire
+    /// for empty lines.
     ///
-    /// If TAB is pressed by the user, the callback function `completion` is asked
-    /// for possible argument completion. If it returns exactly 1 completion, it
-    /// is used, if it returns more, they are displayed.
+    /// ```
+    /// use shli::Prompt;
     ///
-    /// If Ctrl+C is pressed, this function returns `Err(Error::new(ErrorKind::Other, "Ctrl-C pressed.")`,
-    /// while an EOF of `stdin` or Ctrl+D will return the error type `ErrorKind::UnexpectedEof`.
+    /// let p = Prompt::new("> ".to_string(), vec![])
+    ///     .with_line_callback(|line| eprintln!("audit: {}", line));
+    /// ```
+    pub fn with_line_callback(mut self, callback: impl FnMut(&str) + 'static) -> Prompt {
+        self.line_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Transform the components of every accepted command line with
+    /// `postprocessor`, e.g. to expand aliases.
     ///
-    /// On success, `read_commandline` returns a Vector of command line components (command + arguments).
+    /// It runs only if a command line was read successfully, just before
+    /// `read_commandline` (or any other reading method) returns it. The history
+    /// and the line callback get the command line as typed.
     ///
-    /// For example, the following input:
-    /// ```text
-    /// > print out "example command"
     /// ```
-    /// will return `vec!["print", "out", "example command"]`.
-    pub fn read_commandline(&mut self) -> Result<Vec<String>, Error> {
-        let stdout = stdout();
-        let mut stdout = stdout.lock().into_raw_mode()?;
-        let stdin = stdin();
-        let stdin = stdin.lock();
-        write!(stdout, "{}", &self.prompt_text)?;
-        stdout.flush()?;
-        let mut line = String::new();
-        let mut right_line = String::new();
-        let mut history_offset = 0;
-
-        for key in stdin.keys() {
-            match key {
-                Ok(Char('\n')) => break,
-                Ok(Char('\t')) => {
-                    // The tabulator was pressed.
-                    self.completion(&mut stdout, &mut line, &right_line)?
-                }
-                Ok(Char(ch)) => {
-                    line.push(ch);
-                    self.reprint(&mut stdout, &line, &right_line)?
-                }
-                Ok(Key::Left) => {
-                    if let Some(ch) = line.pop() {
-                        right_line = format!("{}{}", ch, right_line);
-                        write!(stdout, "{}", cursor::Left(1))?;
-                        stdout.flush()?
-                    }
-                }
-                Ok(Key::Right) => {
-                    if !right_line.is_empty() {
-                        line.push(right_line.remove(0));
-                        write!(stdout, "{}", cursor::Right(1))?;
-                        stdout.flush()?
-                    }
-                }
-                Ok(Key::Home) => {
-                    right_line = format!("{}{}", line, right_line);
-                    line = String::new();
-                    self.reprint(&mut stdout, &line, &right_line)?;
-                }
-                Ok(Key::End) => {
-                    line.push_str(&right_line);
-                    right_line = String::new();
-                    self.reprint(&mut stdout, &line, &right_line)?;
-                }
-                Ok(Key::Up) => {
-                    if history_offset < self.history.len() {
-                        history_offset += 1;
-                        if let Some(new_cmd_line) =
-                            self.history.get(self.history.len() - history_offset)
-                        {
-                            self.replace_cmdline(
-                                &mut stdout,
-                                new_cmd_line,
-                                &mut line,
-                                &mut right_line,
-                            )?;
-                        }
-                    }
-                }
-                Ok(Key::Down) => {
-                    match history_offset {
-                        1 => {
-                            history_offset = 0;
-                            self.replace_cmdline(&mut stdout, "", &mut line, &mut right_line)?;
-                        }
-                        hoff if hoff > 1 => {
-                            history_offset -= 1;
-
-                            if let Some(new_cmd_line) =
-                                self.history.get(self.history.len() - history_offset)
-                            {
-                                self.replace_cmdline(
-                                    &mut stdout,
-                                    new_cmd_line,
-                                    &mut line,
-                                    &mut right_line,
-                                )?;
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                Ok(Ctrl('c')) => return Err(Error::CtrlC),
-                Ok(Ctrl('d')) => return Err(Error::CtrlD),
-                Ok(Key::Backspace) => {
-                    if line.pop().is_some() {
-                        self.reprint(&mut stdout, &line, &format!("{} ", right_line))?;
-                    }
-                }
-                Ok(Alt('\u{7f}')) => {
-                    // ALT+← was pressed.
-                    // Remove the last word.
-                    let mut words = split(&line);
-                    if words.pop().is_some() {
-                        let old_len = line.len();
-                        // Build up the cmdline again
-                        line = String::new();
-                        for word in words {
-                            line.push_str(&word);
-                            line.push(' ');
-                        }
-                        // Wipe removed characters
-                        if line.len() < old_len {
-                            write!(
-                                stdout,
-                                "{}{}",
-                                cursor::Left((old_len - line.len()) as u16),
-                                " ".repeat(old_len)
-                            )?;
-                        }
-                        // Now display the new cmdline
-                        self.reprint(&mut stdout, &line, &right_line)?;
-                    }
-                }
-                Ok(_) => {}
-                Err(e) => return Err(Error::IoError(e)),
-            }
+    /// use shli::Prompt;
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![]).with_postprocessor(|mut words| {
+    ///     if words.first().is_some_and(|word| word == "ll") {
+    ///         words.splice(..1, ["ls".to_string(), "-l".to_string()]);
+    ///     }
+    ///     words
+    /// });
+    /// ```
+    pub fn with_postprocessor(
+        mut self,
+        postprocessor: impl Fn(Vec<String>) -> Vec<String> + 'static,
+    ) -> Prompt {
+        self.postprocessor = Some(Box::new(postprocessor));
+        self
+    }
+
+    /// Record the exit status of the command last read and run by the caller.
+    ///
+    /// The Prompt doesn't use it itself, but keeps it for customizing the
+    /// prompt text, as there is no prompt closure which could receive it:
+    /// ```
+    /// use shli::Prompt;
+    ///
+    /// let mut p = Prompt::new("> ".to_string(), vec![]);
+    /// p.set_last_status(1);
+    /// // Before reading the next command line
+    /// p.prompt_text = match p.last_status() {
+    ///     Some(0) | None => "> ".to_string(),
+    ///     Some(_) => "\x1b[31m>\x1b[0m ".to_string(),
+    /// };
+    /// ```
+    pub fn set_last_status(&mut self, code: i32) {
+        self.last_status = Some(code);
+    }
+
+    /// The exit status given to `set_last_status`, `None` if there is none yet
+    pub fn last_status(&self) -> Option<i32> {
+        self.last_status
+    }
+
+    /// The command line the user had typed when the most recent read ended
+    /// with `Error::CtrlC`, e.g. to offer running it anyway.
+    ///
+    /// Reading the next command line resets it to `None`.
+    ///
+    /// ```no_run
+    /// use shli::{Error, Prompt};
+    ///
+    /// let mut p = Prompt::new("> ".to_string(), vec![]);
+    /// if let Err(Error::CtrlC) = p.read_commandline() {
+    ///     if let Some(line) = p.cancelled_line() {
+    ///         println!("Cancelled `{}`", line);
+    ///     }
+    /// }
+    /// ```
+    pub fn cancelled_line(&self) -> Option<&str> {
+        self.cancelled.as_deref()
+    }
+
+    /// Replace the history by `entries`, ordered from oldest to newest,
+    /// e.g. to restore it from a database.
+    ///
+    /// Empty entries are dropped, as empty command lines never make it into
+    /// the history. A history entry chosen by Ctrl+O for the next command line
+    /// is forgotten.
+    ///
+    /// ```
+    /// use shli::Prompt;
+    ///
+    /// let mut p = Prompt::new("> ".to_string(), vec![]);
+    /// p.set_history(vec!["ls".to_string(), String::new(), "cd /".to_string()]);
+    /// assert_eq!(p.history, vec!["ls", "cd /"]);
+    /// ```
+    pub fn set_history(&mut self, entries: Vec<String>) {
+        self.history = entries;
+        self.history.retain(|entry| !entry.is_empty());
+        self.next_history = None;
+    }
+
+    /// Change the prompt text, which is written before the user input.
+    ///
+    /// Unlike assigning `prompt_text`, this also updates the cached width of
+    /// the prompt text right away.
+    pub fn set_prompt(&mut self, text: impl Into<String>) {
+        self.prompt_text = text.into();
+        self.prompt_width = visible_width(&self.prompt_text);
+    }
+
+    /// The width of the terminal in columns, as used for laying out
+    /// completions, or 80 if unknown, e.g. if stdout isn't a terminal.
+    ///
+    /// ```
+    /// use shli::Prompt;
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![]);
+    /// println!("{}", "-".repeat(p.terminal_width()));
+    /// ```
+    pub fn terminal_width(&self) -> usize {
+        width() as usize
+    }
+
+    /// The prompt text of the current terminal line
+    fn current_prompt(&self) -> &str {
+        match self.continued {
+            Some(_) => &self.continuation_prompt,
+            None => &self.prompt_text,
         }
-        line.push_str(&right_line);
-        if !line.is_empty() {
-            self.history.push(line.clone());
+    }
+
+    /// The number of terminal columns `current_prompt` occupies
+    fn current_prompt_width(&self) -> usize {
+        match self.continued {
+            Some(_) => visible_width(&self.continuation_prompt),
+            None => self.prompt_width,
         }
-        Ok(split(&line))
+    }
+
+    /// Reprint the command line in the current terminal line.
+    /// `right_line` refers to the command part supposed to be right from the cursor.
+    fn reprint<W: Write>(&self, out: &mut W, line: &str, right_line: &str) -> std::io::Result<()> {
+        self.draw(out, line, right_line, true)
+    }
+
+    /// Redraw the command line after an edit, like `reprint`, but without
+    /// writing the prompt again, as it is still in front of the command line.
+    ///
+    /// The prompt is written anyway if it may change, i.e. with a validator,
+    /// or if the command line doesn't fit into the terminal line.
+    fn redraw<W: Write>(&self, out: &mut W, line: &str, right_line: &str) -> std::io::Result<()> {
+        self.draw(out, line, right_line, false)
+    }
+
+    /// Draw the command line for `reprint` and `redraw`
+    fn draw<W: Write>(
+        &self,
+        out: &mut W,
+        line: &str,
+        right_line: &str,
+        with_prompt: bool,
+    ) -> std::io::Result<()> {
+        let suggestion = match self.typing {
+            true => "",
+            false => self.suggestion(line, right_line).unwrap_or_default(),
+        };
+        let invalid = self.validate(line, right_line).is_some();
+        let line = &self.echoed(line);
+        let right_line = &self.echoed(right_line);
+        let prompt_width = self.current_prompt_width();
+        let keep_prompt = !with_prompt
+            && self.validator.is_none()
+            && prompt_width
+                + visible_width(line)
+                + visible_width(right_line)
+                + visible_width(suggestion)
+                < self.terminal_width();
+        if keep_prompt {
+            write!(out, "\r")?;
+            if prompt_width > 0 {
+                write!(out, "{}", cursor::Right(prompt_width as u16))?;
+            }
+        } else if invalid {
+            write!(
+                out,
+                "\r{}{}{}",
+                INVALID_COLOR,
+                self.current_prompt(),
+                style::Reset
+            )?;
+        } else {
+            write!(out, "\r{}", self.current_prompt())?;
+        }
+        match &self.highlighter {
+            Some(highlight) if self.echo.is_none() => {
+                write!(out, "{}", highlight(&format!("{}{}", line, right_line)))?
+            }
+            _ => write!(out, "{}{}", line, right_line)?,
+        }
+        if !suggestion.is_empty() {
+            write!(out, "{}{}{}", style::Faint, suggestion, style::Reset)?;
+        }
+        write!(out, "{}", clear::UntilNewline)?;
+        // The suggestion is not part of the command line, so the cursor goes back over it.
+        let right_width = visible_width(right_line) + visible_width(suggestion);
+        if right_width > 0 {
+            write!(out, "{}", cursor::Left(right_width as u16))?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// The message of the validator if the whole command line is invalid,
+    /// see `Prompt::with_validator`
+    fn validate(&self, line: &str, right_line: &str) -> Option<String> {
+        let validator = self.validator.as_ref()?;
+        let continued = self.continued.as_deref().unwrap_or_default();
+        match validator(&format!("{}{}{}", continued, line, right_line)) {
+            Validation::Valid => None,
+            Validation::Invalid(message) => Some(message),
+        }
+    }
+
+    /// `text` as shown on the terminal, see `Prompt::with_echo`.
+    ///
+    /// Without an echo, control characters inserted by Ctrl+V are shown in
+    /// caret notation, like `^I` for a tabulator.
+    fn echoed<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match &self.echo {
+            Some(echo) => Cow::Owned(text.chars().filter_map(echo).collect()),
+            None if text.contains(|ch: char| ch.is_ascii_control()) => {
+                Cow::Owned(text.chars().map(caret_notation).collect())
+            }
+            None => Cow::Borrowed(text),
+        }
+    }
+
+    /// Redraw after the text left from the cursor only changed at its end.
+    ///
+    /// `erased` was removed left from the cursor, then `inserted` was inserted. Only the line from the first changed column on is rewritten,
+    /// which is much cheaper than `reprint` for long lines.
+    /// This only works if the line is drawn as it is, see `can_redraw_tail`.
+    fn redraw_tail<W: Write>(
+        &self,
+        out: &mut W,
+        erased: &str,
+        inserted: &str,
+        right_line: &str,
+    ) -> std::io::Result<()> {
+        let erased = visible_width(&self.echoed(erased));
+        if erased > 0 {
+            write!(out, "{}", cursor::Left(erased as u16))?;
+        }
+        let right_line = &self.echoed(right_line);
+        write!(out, "{}{}", self.echoed(inserted), right_line)?;
+        if erased > 0 {
+            write!(out, "{}", clear::UntilNewline)?;
+        }
+        let right_width = visible_width(right_line);
+        if right_width > 0 {
+            write!(out, "{}", cursor::Left(right_width as u16))?;
+        }
+        out.flush()
+    }
+
+    /// Whether the line on screen is the plain command line, so that
+    /// `redraw_tail` can be used instead of `reprint`
+    fn can_redraw_tail(&self) -> bool {
+        self.highlighter.is_none()
+            && self.echo.is_none()
+            && self.validator.is_none()
+            && !self.autosuggest
+    }
+
+    /// The most recent history entry, if the history is enabled
+    fn last_entry(&self) -> Option<&String> {
+        self.history.last().filter(|_| self.history_enabled)
+    }
+
+    /// The rest of the most recent history entry starting with `line`,
+    /// if `autosuggest` is enabled and the cursor is at the end of the command line.
+    ///
+    /// With `suggestion_delay`, there is none unless it is shown.
+    pub(crate) fn suggestion(&self, line: &str, right_line: &str) -> Option<&str> {
+        if !self.autosuggest
+            || (self.suggestion_delay.is_some() && !self.suggestion_shown)
+            || !self.history_enabled
+            || self.echo.is_some()
+            || line.is_empty()
+            || !right_line.is_empty()
+        {
+            return None;
+        }
+        self.history
+            .iter()
+            .rev()
+            .find(|entry| entry.len() > line.len() && entry.starts_with(line))
+            .map(|entry| &entry[line.len()..])
+    }
+
+    /// Append the current suggestion to the command line, if there is one.
+    fn accept_suggestion(&self, line: &mut String, right_line: &str) {
+        if let Some(suggestion) = self.suggestion(line, right_line) {
+            let suggestion = suggestion.to_string();
+            line.push_str(&suggestion);
+        }
+    }
+
+    /// The completions for the command line `line`, as offered by tab completion.
+    ///
+    /// `line` is the text left from the cursor. Nothing is printed.
+    /// ```
+    /// use shli::{Command, Prompt};
+    /// use shli::completion::CompletionResult;
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![Command::new("exit")]);
+    /// assert_eq!(
+    ///     p.complete_line("ex"),
+    ///     CompletionResult::PossibilityList(vec!["exit".to_string()])
+    /// );
+    /// ```
+    pub fn complete_line(&self, line: &str) -> CompletionResult {
+        let result = if self.sort_completions {
+            complete(line, &self.commands)
+        } else {
+            complete_in_order(line, &self.commands)
+        };
+        if self.rank_completions && self.history_enabled {
+            rank_by_history(result, line, &self.history)
+        } else {
+            result
+        }
+    }
+
+    /// The completions tab completion offers with the cursor at byte position
+    /// `cursor` of the command line `line`.
+    ///
+    /// Only the text left from the cursor is completed, like by `complete_line`.
+    /// A `cursor` beyond the end of `line` stands for its end.
+    /// Panics if `cursor` is not at a character boundary of `line`.
+    /// ```
+    /// use shli::{Command, Prompt};
+    /// use shli::completion::CompletionResult;
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![Command::new("exit")]);
+    /// assert_eq!(
+    ///     p.completions_at("ex foo", 2),
+    ///     CompletionResult::PossibilityList(vec!["exit".to_string()])
+    /// );
+    /// ```
+    pub fn completions_at(&self, line: &str, cursor: usize) -> CompletionResult {
+        self.complete_line(&line[..cursor.min(line.len())])
+    }
+
+    /// Complete the word left from the cursor.
+    ///
+    /// If the cursor is within a word, its part right from the cursor is
+    /// replaced as well, and the cursor is placed behind the completion.
+    /// If there are more than `completion_query_items` possibilities, they are
+    /// kept in the returned `CompletionState::Querying` until the user confirms
+    /// listing them.
+    fn completion<W: Write>(
+        &self,
+        out: &mut W,
+        line: &mut String,
+        right_line: &mut String,
+        repeated: bool,
+    ) -> std::io::Result<CompletionState> {
+        let result = if self.catch_completion_panics {
+            // The panic message was printed by the panic hook, so draw the line again
+            match panic::catch_unwind(AssertUnwindSafe(|| self.complete_line(line))) {
+                Ok(result) => result,
+                Err(_) => {
+                    write!(out, "\x07")?;
+                    self.reprint(out, line, right_line)?;
+                    return Ok(CompletionState::Completed);
+                }
+            }
+        } else {
+            self.complete_line(line)
+        };
+        let possibilities = match result {
+            CompletionResult::None => {
+                self.ring_bell(out)?;
+                return Ok(CompletionState::Completed);
+            }
+            description @ CompletionResult::Description(_) => {
+                out.write_all(&self.renderer.render(&description, width()))?;
+                write!(out, "\n\r")?;
+                self.reprint(out, line, right_line)?;
+                return Ok(CompletionState::Completed);
+            }
+            CompletionResult::PossibilityList(possible_words) => possible_words
+                .into_iter()
+                .map(|word| (word, String::new()))
+                .collect(),
+            CompletionResult::DescribedList(possibilities) => possibilities,
+        };
+        if possibilities.len() == 1 {
+            // Replace the component being typed, leaving the rest as it is
+            line.truncate(current_component_start(line));
+            drain_component_suffix(line, right_line);
+            line.push_str(&quote(&possibilities[0].0));
+            if !is_partial(&possibilities[0].0) {
+                line.push(' ');
+            }
+            // Now display the new cmdline
+            self.reprint(out, line, right_line)?;
+        } else if possibilities.is_empty() {
+            self.ring_bell(out)?;
+        } else if self.complete_prefix_first && !self.completes_to(line, &possibilities) {
+            // Insert the common prefix, as it gets further
+            let prefix = common_prefix(possibilities.iter().map(|(word, _)| word.as_str()));
+            line.truncate(current_component_start(line));
+            drain_component_suffix(line, right_line);
+            line.push_str(&quote(prefix));
+            self.reprint(out, line, right_line)?;
+        } else if self.complete_prefix_first && !repeated {
+            // The possibilities are listed when pressing the completion key again
+            self.ring_bell(out)?;
+        } else {
+            // Display the possibilities
+            let displayed: Vec<(String, String)> = possibilities
+                .into_iter()
+                .map(|(word, description)| (display_text(line, &word, &self.commands), description))
+                .collect();
+            match self.completion_query_items {
+                Some(max) if displayed.len() > max => {
+                    write!(
+                        out,
+                        "\n\rDisplay all {} possibilities? (y/n)",
+                        displayed.len()
+                    )?;
+                    out.flush()?;
+                    return Ok(CompletionState::Querying(displayed));
+                }
+                _ => self.list_completions(out, line, right_line, &displayed)?,
+            }
+        }
+        Ok(CompletionState::Completed)
+    }
+
+    /// Whether the component being typed at the end of `line` already is the
+    /// common prefix of `possibilities`, so completing it doesn't get further
+    fn completes_to(&self, line: &str, possibilities: &[(String, String)]) -> bool {
+        let prefix = common_prefix(possibilities.iter().map(|(word, _)| word.as_str()));
+        line[current_component_start(line)..] == quote(prefix)
+    }
+
+    /// Signal that there is nothing to complete, according to `bell_style`
+    fn ring_bell<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        match &self.bell_style {
+            BellStyle::None => return Ok(()),
+            BellStyle::Audible => write!(out, "\x07")?,
+            BellStyle::Visible(sequence) => write!(out, "{}", sequence)?,
+        }
+        out.flush()
+    }
+
+    /// Print `displayed` completions with their descriptions below the command line,
+    /// using the completion renderer.
+    fn list_completions<W: Write>(
+        &self,
+        out: &mut W,
+        line: &str,
+        right_line: &str,
+        displayed: &[(String, String)],
+    ) -> std::io::Result<()> {
+        let result = if displayed
+            .iter()
+            .all(|(_, description)| description.is_empty())
+        {
+            CompletionResult::PossibilityList(
+                displayed.iter().map(|(word, _)| word.clone()).collect(),
+            )
+        } else {
+            CompletionResult::DescribedList(displayed.to_vec())
+        };
+        out.write_all(&self.renderer.render(&result, width()))?;
+        write!(out, "\n\r")?;
+        self.reprint(out, line, right_line)
+    }
+
+    /// Clear the current terminal line and move the cursor to its start.
+    ///
+    /// This may be used to erase the prompt after reading a command line:
+    /// ```
+    /// use shli::Prompt;
+    ///
+    /// let p = Prompt::new("> ".to_string(), vec![]);
+    /// let mut output = vec![];
+    /// p.clear_line(&mut output).unwrap();
+    /// assert_eq!(output, b"\r\x1b[2K");
+    /// ```
+    pub fn clear_line<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        write!(out, "\r{}", clear::CurrentLine)
+    }
+
+    /// Convenience function to replace the current edit buffer while prompting
+    ///
+    /// All lines of a multi-line command line are replaced.
+    fn replace_cmdline<W: Write>(
+        &mut self,
+        out: &mut W,
+        new_cmd_line: &str,
+        line: &mut String,
+        right_line: &mut String,
+    ) -> Result<(), Error> {
+        let rows = self.rows_above_cursor(line);
+        if rows > 0 {
+            write!(out, "{}", cursor::Up(rows as u16))?;
+        }
+        // Clear everything from the first line of the command line on
+        write!(out, "\r{}", clear::AfterCursor)?;
+        self.continued = None;
+        self.continued_rows = 0;
+        let (rows, last_row) = match new_cmd_line.rsplit_once('\n') {
+            Some((rows, last_row)) => (Some(rows), last_row),
+            None => (None, new_cmd_line),
+        };
+        for row in rows.into_iter().flat_map(|rows| rows.split('\n')) {
+            write!(out, "\r{}{}", self.current_prompt(), self.echoed(row))?;
+            self.continue_line(out, &format!("{}\n", row))?;
+        }
+        *line = String::from(last_row);
+        *right_line = String::new();
+        self.reprint(out, line, right_line)?;
+        Ok(())
+    }
+
+    /// The number of terminal lines of the command line above the cursor, which
+    /// is behind `line`: the continued lines, and the lines `line` wraps into.
+    fn rows_above_cursor(&self, line: &str) -> usize {
+        let columns = self.current_prompt_width() + visible_width(&self.echoed(line));
+        self.continued_rows + columns.saturating_sub(1) / (width() as usize).max(1)
+    }
+
+    /// Freeze `text` as part of the command line and go on in the next terminal line.
+    ///
+    /// The rest of the current terminal line is cleared. The cursor is moved
+    /// down, the next `reprint` moves it to the start of the line.
+    fn continue_line<W: Write>(&mut self, out: &mut W, text: &str) -> std::io::Result<()> {
+        // A line longer than the terminal wraps into several rows
+        let shown = self.echoed(text.trim_end_matches('\n'));
+        let columns = self.current_prompt_width() + visible_width(&shown);
+        self.continued_rows += columns.saturating_sub(1) / (width() as usize).max(1) + 1;
+        self.continued
+            .get_or_insert_with(String::new)
+            .push_str(text);
+        writeln!(out, "{}", clear::UntilNewline)
+    }
+
+    /// Delete `count` characters right from the cursor (Delete)
+    fn delete_forward<W: Write>(
+        &self,
+        out: &mut W,
+        line: &str,
+        right_line: &mut String,
+        count: usize,
+    ) -> std::io::Result<()> {
+        let old_len = right_line.len();
+        for _ in 0..count.min(right_line.chars().count()) {
+            right_line.remove(0);
+        }
+        if right_line.len() < old_len {
+            self.redraw(out, line, right_line)?;
+        }
+        Ok(())
+    }
+
+    /// Move `killed` into the kill buffer, unless nothing was killed.
+    fn kill(&mut self, killed: String) {
+        if !killed.is_empty() {
+            self.kill_buffer = killed;
+        }
+    }
+
+    /// Kill the text right from the cursor (Ctrl+K)
+    pub(crate) fn kill_to_end(&mut self, right_line: &mut String) {
+        self.kill(std::mem::take(right_line))
+    }
+
+    /// Kill the text left from the cursor (Ctrl+U)
+    pub(crate) fn kill_to_start(&mut self, line: &mut String) {
+        self.kill(std::mem::take(line))
+    }
+
+    /// Kill the `count` whitespace delimited words left from the cursor (Ctrl+W)
+    pub(crate) fn kill_word_backward(&mut self, line: &mut String, count: usize) {
+        let mut word_start = line.len();
+        for _ in 0..count {
+            word_start = prev_word_boundary(line, word_start);
+        }
+        let killed = line.split_off(word_start);
+        self.kill(killed)
+    }
+
+    /// Kill from the cursor to the end of the `count`th word (Alt+D)
+    pub(crate) fn kill_word_forward(&mut self, line: &str, right_line: &mut String, count: usize) {
+        let text = format!("{}{}", line, right_line);
+        let mut killed_end = line.len();
+        for _ in 0..count {
+            killed_end = next_word_boundary(&text, killed_end);
+        }
+        let rest = right_line.split_off(killed_end - line.len());
+        let killed = std::mem::replace(right_line, rest);
+        self.kill(killed)
+    }
+
+    /// Insert the kill buffer at the cursor (Ctrl+Y)
+    pub(crate) fn yank(&self, line: &mut String) {
+        line.push_str(&self.kill_buffer);
+    }
+
+    /// Apply a command of vi's normal mode to the command line.
+    ///
+    /// In normal mode, the cursor is on the first character of `right_line`.
+    pub(crate) fn apply_vi_command(
+        &mut self,
+        command: ViCommand,
+        line: &mut String,
+        right_line: &mut String,
+    ) {
+        match command {
+            ViCommand::PassThrough | ViCommand::Nothing => {}
+            ViCommand::MoveLeft => {
+                if let Some(ch) = line.pop() {
+                    right_line.insert(0, ch);
+                }
+            }
+            ViCommand::MoveRight => {
+                if !right_line.is_empty() {
+                    line.push(right_line.remove(0));
+                }
+            }
+            ViCommand::WordForward => {
+                let pos = next_word_start(&format!("{}{}", line, right_line), line.len());
+                set_cursor(line, right_line, pos);
+            }
+            ViCommand::WordBackward => {
+                let pos = prev_word_boundary(&format!("{}{}", line, right_line), line.len());
+                set_cursor(line, right_line, pos);
+            }
+            ViCommand::StartOfLine => set_cursor(line, right_line, 0),
+            ViCommand::EndOfLine => {
+                line.push_str(right_line);
+                *right_line = line.pop().map(String::from).unwrap_or_default();
+            }
+            ViCommand::DeleteChar => {
+                if !right_line.is_empty() {
+                    right_line.remove(0);
+                }
+            }
+            ViCommand::KillToEnd => self.kill_to_end(right_line),
+            ViCommand::KillLine => {
+                line.push_str(right_line);
+                right_line.clear();
+                self.kill_to_start(line);
+            }
+        }
+    }
+
+    /// Edit the command line in an external editor (Ctrl+X Ctrl+E)
+    ///
+    /// Raw mode is suspended while the editor runs. If the editor exits with
+    /// a non-zero status, the command line is left untouched.
+    fn edit_in_editor<W: Write>(
+        &self,
+        out: &mut W,
+        terminal: Option<&RawMode>,
+        line: &mut String,
+        right_line: &mut String,
+    ) -> Result<(), Error> {
+        write!(out, "\n\r")?;
+        out.flush()?;
+        if let Some(terminal) = terminal {
+            terminal.suspend()?;
+        }
+        let edited = edit_externally(&format!("{}{}", line, right_line), &editors());
+        if let Some(terminal) = terminal {
+            terminal.activate()?;
+        }
+        if let Some(edited) = edited? {
+            *line = edited;
+            *right_line = String::new();
+        }
+        self.reprint(out, line, right_line)?;
+        Ok(())
+    }
+
+    /// Prompt for a single command line.
+    ///
+    /// This function reads and returns a command line.
+    /// Line editing with backspace and ALT+backspace is supported.
+    /// Ctrl+K, Ctrl+U and Ctrl+W kill the text right from the cursor, left from
+    /// the cursor and the word left from the cursor, respectively.
+    /// Alt+D kills the word right from the cursor.
+    /// Alt+U, Alt+L and Alt+C uppercase, lowercase and capitalize the word
+    /// right from the cursor, moving the cursor behind it.
+    /// Ctrl+Y inserts the most recently killed text at the cursor.
+    ///
+    /// Ctrl+J and Ctrl+M, which some terminals send for Enter, work just like Enter.
+    ///
+    /// Ctrl+V inserts the next key literally, e.g. a tabulator instead of
+    /// completing. Control keys insert their ASCII control character, like
+    /// `'\x01'` for Ctrl+A, which is shown in caret notation (`^A`). Enter
+    /// inserts a line break like Alt+Enter. Other keys insert nothing.
+    ///
+    /// Ctrl+O submits a command line recalled from the history like Enter, and
+    /// starts the next command line with the history entry following it.
+    /// This way, a sequence of commands may be run again.
+    ///
+    /// A command line ending with a backslash is continued on the next line,
+    /// after `continuation_prompt`. The backslash and the line break are removed.
+    ///
+    /// Alt+Enter (or the key set by `with_newline_key`) inserts a line break
+    /// into the command line, which then continues in the next terminal line.
+    /// Line breaks separate components just like spaces, and are kept in the
+    /// history entry as they are.
+    ///
+    /// Up and Down browse the history entries starting with the text typed
+    /// before, or all entries if nothing was typed. Going down past the newest
+    /// entry restores the typed text.
+    ///
+    /// When `autosuggest` is enabled, Right and End (or Ctrl+E) at the end of
+    /// the command line accept the dimmed suggestion taken from the history.
+    ///
+    /// With `edit_mode` set to `EditMode::Vi`, Esc enters vi's normal mode, in
+    /// which `h`/`l` move, `w`/`b` move by word, `0`/`$` jump to the ends, `x`
+    /// deletes, `dd`/`D` kill and `i`/`a` return to insert mode.
+    ///
+    /// Alt and a number (e.g. Alt+3) given before an editing key repeats it
+    /// that often: inserting a character, moving, Backspace, Delete and the
+    /// word deletions. A number before any other key is discarded.
+    ///
+    /// Ctrl+X Ctrl+E opens the command line in `$EDITOR` (falling back to `vi`
+    /// and `nano`). When the editor exits successfully, the saved text becomes
+    /// the new command line. Otherwise, the command line is kept as it was.
+    ///
+    /// If TAB (or the key set by `with_completion_key`) is pressed by the user,
+    /// the callback function `completion` is asked
+    /// for possible argument completion. If it returns exactly 1 completion, it
+    /// is used, if it returns more, they are displayed.
+    ///
+    /// If Ctrl+C is pressed, this function returns `Err(Error::CtrlC)`, see also
+    /// `raise_sigint`. Ctrl+D ends reading with `Err(Error::CtrlD)` only on an
+    /// empty command line, like the EOF of piped input. Otherwise, it does what
+    /// `ctrl_d_action` says, which is `CtrlDAction::DeleteChar` by default.
+    ///
+    /// If stdin is not a terminal (e.g. input is piped in), a plain line is read
+    /// instead, without prompt, editing or completion. On dumb terminals
+    /// (`TERM=dumb`), `read_line_simple` is used.
+    ///
+    /// On success, `read_commandline` returns a Vector of command line components (command + arguments).
+    ///
+    /// For example, the following input:
+    /// ```text
+    /// > print out "example command"
+    /// ```
+    /// will return `vec!["print", "out", "example command"]`.
+    pub fn read_commandline(&mut self) -> Result<Vec<String>, Error> {
+        self.read_commandline_raw().map(|(_, words)| words)
+    }
+
+    /// Prompt for a single command line like `read_commandline`, and return
+    /// the command line exactly as typed along with its components.
+    ///
+    /// The raw command line keeps quotes and spacing, but excludes the
+    /// trailing newline.
+    pub fn read_commandline_raw(&mut self) -> Result<(String, Vec<String>), Error> {
+        self.read_checked(&mut stdout().lock(), &no_check)
+    }
+
+    /// Prompt for a single command line like `read_commandline`, but write the
+    /// prompt, the command line and the completions to `out` instead of stdout.
+    ///
+    /// Keys are still read from stdin. Raw mode is entered for the terminal
+    /// of the process, which both terminal backends only support for
+    /// stdin/stdout, so `out` should be shown on that terminal as well.
+    pub fn read_commandline_on<W: Write>(&mut self, out: &mut W) -> Result<Vec<String>, Error> {
+        self.read_checked(out, &no_check).map(|(_, words)| words)
+    }
+
+    /// Prompt for a command line of `min` to `max` components (or at least
+    /// `min` if `max` is `None`), command included.
+    ///
+    /// The components are counted after splitting the command line.
+    /// If their number is out of range, an error is shown below the command
+    /// line, which may then be edited further. Ctrl+C or Ctrl+D abort reading,
+    /// returning `Error::CtrlC` or `Error::CtrlD` respectively.
+    pub fn read_args(&mut self, min: usize, max: Option<usize>) -> Result<Vec<String>, Error> {
+        self.read_checked(&mut stdout().lock(), &|words| {
+            check_arg_count(words, min, max)
+        })
+        .map(|(_, words)| words)
+    }
+
+    /// Read a command line passing `check` from stdin, in the way fitting the terminal,
+    /// writing to `out`.
+    fn read_checked<W: Write>(
+        &mut self,
+        out: &mut W,
+        check: Check,
+    ) -> Result<(String, Vec<String>), Error> {
+        if !stdin().is_terminal() {
+            return self.read_plain_line(stdin().lock(), out, false, check);
+        }
+        if env::var("TERM").is_ok_and(|term| term == "dumb") {
+            return self.read_plain_line(stdin().lock(), out, true, check);
+        }
+        let terminal = RawModeGuard::new()?;
+        let result = self.read_keys(keys(), out, Some(terminal.raw_mode()), check, key_within);
+        drop(terminal);
+        if self.raise_sigint && matches!(result, Err(Error::CtrlC)) {
+            raise_interrupt();
+        }
+        result
+    }
+
+    /// Ask a yes/no `question` and read the answer as a single keypress.
+    ///
+    /// `y` and `n` (in any case) answer the question, while Enter answers with
+    /// `default`. Other keys are ignored. The answer is echoed, followed by a
+    /// line break. Ctrl+C returns `Error::CtrlC` and Ctrl+D `Error::CtrlD`.
+    ///
+    /// If stdin is not a terminal, a line is read instead, answering with
+    /// `default` unless it starts with `y` or `n`.
+    ///
+    /// ```no_run
+    /// use shli::Prompt;
+    ///
+    /// let mut p = Prompt::new("> ".to_string(), vec![]);
+    /// if p.confirm("Delete file? [y/N] ", false).unwrap() {
+    ///     // …
+    /// }
+    /// ```
+    pub fn confirm(&mut self, question: &str, default: bool) -> Result<bool, Error> {
+        let mut stdout = stdout().lock();
+        if !stdin().is_terminal() {
+            write!(stdout, "{}", question)?;
+            stdout.flush()?;
+            let mut answer = String::new();
+            if stdin().read_line(&mut answer)? == 0 {
+                return Err(Error::CtrlD);
+            }
+            return Ok(match answer.trim_start().chars().next() {
+                Some('y' | 'Y') => true,
+                Some('n' | 'N') => false,
+                _ => default,
+            });
+        }
+        let _terminal = RawModeGuard::new()?;
+        read_confirmation(keys(), &mut stdout, question, default)
+    }
+
+    /// Prompt for a single command line without raw mode.
+    ///
+    /// The prompt is printed and a line is read from stdin, like the terminal
+    /// does it. There is no cursor control, editing or completion.
+    /// Accepted lines are recorded in the history like by `read_commandline`.
+    /// EOF returns `Error::CtrlD`.
+    pub fn read_line_simple(&mut self) -> Result<Vec<String>, Error> {
+        self.read_plain_line(stdin().lock(), &mut stdout().lock(), true, &no_check)
+            .map(|(_, words)| words)
+    }
+
+    /// Read command lines from `input` without any terminal handling, until
+    /// one passes `check`.
+    ///
+    /// This is used when stdin is not a terminal, e.g. when input is piped in.
+    /// There is no echo, editing or completion, and the prompt is only
+    /// written to `out` if `show_prompt` is set. EOF returns `Error::CtrlD`.
+    pub(crate) fn read_plain_line<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        out: &mut W,
+        show_prompt: bool,
+        check: Check,
+    ) -> Result<(String, Vec<String>), Error> {
+        loop {
+            if show_prompt {
+                write!(out, "{}", self.prompt_text)?;
+                out.flush()?;
+            }
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Err(Error::CtrlD);
+            }
+            let len = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(len);
+            let repeated = line.is_empty() && self.empty_line == EmptyLineBehavior::RepeatLast;
+            if line.is_empty() {
+                match (self.empty_line, self.last_entry()) {
+                    (EmptyLineBehavior::Ignore, _) => continue,
+                    (EmptyLineBehavior::RepeatLast, Some(last)) => line = last.clone(),
+                    _ => {}
+                }
+            }
+            if let Some(message) = self.validate(&line, "") {
+                writeln!(out, "{}", message)?;
+                if self.invalid_line == InvalidLineBehavior::Reject {
+                    continue;
+                }
+            }
+            match check(&self.split_line(&line)) {
+                Ok(()) => return Ok(self.accept(line, repeated)),
+                Err(message) => writeln!(out, "{}", message)?,
+            }
+        }
+    }
+
+    /// Record the accepted `line` in the history, pass it to the line callback
+    /// and split it into its components.
+    ///
+    /// If `repeated`, the line repeats the last history entry, which isn't
+    /// recorded again.
+    fn accept(&mut self, mut line: String, repeated: bool) -> (String, Vec<String>) {
+        if self.trim_on_submit {
+            line = String::from(trim_delimiters(&line));
+        }
+        if !line.is_empty() {
+            if self.history_enabled && !(repeated && self.last_entry() == Some(&line)) {
+                self.history.push(line.clone());
+            }
+            if let Some(callback) = &mut self.line_callback {
+                callback(&line);
+            }
+        }
+        let mut words = self.split_line(&line);
+        if let Some(postprocessor) = &self.postprocessor {
+            words = postprocessor(words);
+        }
+        (line, words)
+    }
+
+    /// Split the accepted `line` into its components, without its comment
+    fn split_line(&self, line: &str) -> Vec<String> {
+        match &self.comment_marker {
+            Some(marker) => split(strip_comment(line, marker)),
+            None => split(line),
+        }
+    }
+
+    /// Prompt for a single command line, reading `keys` and writing to `out`.
+    ///
+    /// This works exactly like `read_commandline`, but doesn't touch the terminal.
+    /// Therefore, it allows feeding a prepared key sequence, e.g. for testing:
+    /// ```
+    /// use shli::Prompt;
+    /// use shli::Key::Char;
+    ///
+    /// let mut p = Prompt::new("> ".to_string(), vec![]);
+    /// let keys = "print \"a b\"\n".chars().map(|ch| Ok(Char(ch)));
+    /// let mut output = vec![];
+    /// let line = p.read_from(keys, &mut output);
+    /// assert_eq!(line.unwrap(), vec!["print", "a b"]);
+    /// assert!(output.starts_with(b"> "));
+    /// ```
+    ///
+    /// It also suits a terminal the caller already keeps in raw mode, with keys
+    /// from the caller's own stream, see `read_commandline_in_raw_mode`.
+    pub fn read_from<R, W>(&mut self, keys: R, out: &mut W) -> Result<Vec<String>, Error>
+    where
+        R: Iterator<Item = std::io::Result<Key>>,
+        W: Write,
+    {
+        // The next key is always there, but it is drawn key by key
+        self.read_keys(keys, out, None, &no_check, |timeout| !timeout.is_zero())
+            .map(|(_, words)| words)
+    }
+
+    /// Prompt for a single command line on a terminal that is already in raw
+    /// mode, e.g. because the application shows a full-screen TUI, writing to `out`.
+    ///
+    /// Keys are read from stdin like by `read_commandline`, but raw mode is
+    /// neither entered nor left. The caller owns it: the terminal must be in
+    /// raw mode for the whole read, and stays in it afterwards. Therefore, an
+    /// external editor (Ctrl+X Ctrl+E) runs in raw mode as well, and Ctrl+C
+    /// always returns `Error::CtrlC`, regardless of `raise_sigint`.
+    ///
+    /// ```no_run
+    /// use shli::{Prompt, RawModeGuard};
+    ///
+    /// // Held by the application, e.g. while its TUI is shown
+    /// let guard = RawModeGuard::new().expect("stdout is no terminal");
+    /// let mut p = Prompt::new(":".to_string(), vec![]);
+    /// let line = p.read_commandline_in_raw_mode(&mut std::io::stdout());
+    /// drop(guard);
+    /// ```
+    pub fn read_commandline_in_raw_mode<W: Write>(
+        &mut self,
+        out: &mut W,
+    ) -> Result<Vec<String>, Error> {
+        self.read_keys(keys(), out, None, &no_check, key_within)
+            .map(|(_, words)| words)
+    }
+
+    /// Prompt for a single command line, typed as `keys`, and return everything
+    /// written along with the result. Requires the `testing` feature.
+    ///
+    /// Like `read_from`, this doesn't touch the real terminal, so it is meant
+    /// for testing own commands, completions and key bindings:
+    /// ```
+    /// use shli::{Command, Prompt};
+    /// use shli::Key::Char;
+    ///
+    /// let mut p = Prompt::new("> ".to_string(), vec![Command::new("print")]);
+    /// let (output, line) = p.run_keys(vec![Char('p'), Char('\t'), Char('\n')]);
+    /// assert_eq!(line.unwrap(), vec!["print"]);
+    /// assert!(output.starts_with(b"> p"));
+    /// ```
+    #[cfg(feature = "testing")]
+    pub fn run_keys(&mut self, keys: Vec<Key>) -> (Vec<u8>, Result<Vec<String>, Error>) {
+        let mut output = vec![];
+        let result = self.read_from(keys.into_iter().map(Ok), &mut output);
+        (output, result)
+    }
+
+    /// The read loop behind `read_commandline` and `read_from`.
+    ///
+    /// If `terminal` is given, raw mode is suspended while an external editor runs.
+    /// A submitted command line is only accepted if its components pass `check`.
+    /// Otherwise, the error returned by `check` is shown and editing goes on.
+    /// `key_within` tells whether the next key is available within a timeout.
+    pub(crate) fn read_keys<R, W>(
+        &mut self,
+        keys: R,
+        out: &mut W,
+        terminal: Option<&RawMode>,
+        check: Check,
+        key_within: fn(Duration) -> bool,
+    ) -> Result<(String, Vec<String>), Error>
+    where
+        R: Iterator<Item = std::io::Result<Key>>,
+        W: Write,
+    {
+        let mut state = self.begin_line(out)?;
+        for key in keys {
+            state.more_keys = key_within(Duration::ZERO);
+            let submitted = self.process_key(&mut state, key, out, terminal);
+            // Output following an error is not drawn over by the next read
+            self.status_shown &= submitted.is_ok();
+            let submitted = submitted?;
+            if let Some(delay) = self.suggestion_delay {
+                if !submitted && self.typing && !key_within(delay) {
+                    self.show_suggestion(out, &state)?;
+                }
+            }
+            if submitted {
+                if !self.submit_empty(&mut state, out)? || !self.submit_valid(&state, out)? {
+                    continue;
+                }
+                let continued = self.continued.as_deref().unwrap_or_default();
+                let text = format!("{}{}{}", continued, state.line, state.right_line);
+                match check(&self.split_line(&text)) {
+                    Ok(()) => break,
+                    Err(message) => {
+                        write!(out, "\n\r{}\n", message)?;
+                        self.reprint(out, &state.line, &state.right_line)?;
+                    }
+                }
+            }
+        }
+        Ok(self.finish_line(state))
+    }
+
+    /// Print `text` above the command line of an interrupted read, which is
+    /// drawn again below it, keeping the cursor position.
+    ///
+    /// This is meant for output of background tasks while the user is typing,
+    /// e.g. between cancelling `read_commandline_async` in a `tokio::select!`
+    /// and calling it again. Without an interrupted read, `text` is just printed.
+    /// Every line of `text` ends with a line break (`\r\n`).
+    ///
+    /// ```
+    /// use shli::Prompt;
+    ///
+    /// let mut p = Prompt::new("> ".to_string(), vec![]);
+    /// let mut output = vec![];
+    /// p.print_above(&mut output, "Job 1 done\nJob 2 done").unwrap();
+    /// assert_eq!(output, b"Job 1 done\r\nJob 2 done\r\n");
+    /// ```
+    pub fn print_above<W: Write>(&mut self, out: &mut W, text: &str) -> std::io::Result<()> {
+        let interrupted = self.interrupted.take();
+        if interrupted.is_some() || self.status_shown {
+            let line = interrupted.as_ref().map_or("", |state| &state.line);
+            self.clear_interrupted(out, line)?;
+        }
+        for line in text.lines() {
+            write!(out, "{}\r\n", line)?;
+        }
+        if let Some(state) = interrupted {
+            self.redraw_interrupted(out, &state)?;
+            self.interrupted = Some(state);
+        } else if self.status_shown {
+            self.status_shown = false;
+        }
+        out.flush()
+    }
+
+    /// Clear the status line and command line of an interrupted read, in which
+    /// the cursor is behind `line`, moving the cursor to where they started
+    fn clear_interrupted<W: Write>(&self, out: &mut W, line: &str) -> std::io::Result<()> {
+        let rows = self.rows_above_cursor(line) + usize::from(self.status_shown);
+        if rows > 0 {
+            write!(out, "{}", cursor::Up(rows as u16))?;
+        }
+        write!(out, "\r{}", clear::AfterCursor)
+    }
+
+    /// Draw the status line and the command line of an interrupted read again,
+    /// after `clear_interrupted`
+    fn redraw_interrupted<W: Write>(
+        &mut self,
+        out: &mut W,
+        state: &LineState,
+    ) -> std::io::Result<()> {
+        if let Some(status) = &self.status {
+            write!(out, "{}{}\n\r", status, clear::UntilNewline)?;
+            self.status_shown = true;
+        }
+        let continued = self.continued.take().unwrap_or_default();
+        self.continued_rows = 0;
+        for row in continued.split_inclusive('\n') {
+            let shown = self.echoed(row.trim_end_matches('\n'));
+            write!(out, "\r{}{}", self.current_prompt(), shown)?;
+            self.continue_line(out, row)?;
+        }
+        self.reprint(out, &state.line, &state.right_line)
+    }
+
+    /// Print the prompt and return the state of the command line, which is
+    /// empty unless Ctrl+O was pressed before.
+    ///
+    /// The command line of an interrupted read is drawn again and continued.
+    pub(crate) fn begin_line<W: Write>(&mut self, out: &mut W) -> std::io::Result<LineState> {
+        // `prompt_text` may have been assigned directly since
+        self.prompt_width = visible_width(&self.prompt_text);
+        self.typing = false;
+        self.suggestion_shown = false;
+        if let Some(state) = self.interrupted.take() {
+            self.clear_interrupted(out, &state.line)?;
+            self.redraw_interrupted(out, &state)?;
+            return Ok(state);
+        }
+        if let Some(status) = &self.status {
+            if self.status_shown {
+                // Draw over the status line and command line of an interrupted read
+                self.clear_interrupted(out, "")?;
+            }
+            write!(out, "{}{}\n\r", status, clear::UntilNewline)?;
+            self.status_shown = true;
+        }
+        self.continued = None;
+        self.continued_rows = 0;
+        self.cancelled = None;
+        let mut state = LineState::default();
+        if let Some(index) = self.next_history.take() {
+            if let Some(entry) = self.history.get(index) {
+                state.line = entry.clone();
+                state.history_offset = self.history.len() - index;
+            }
+        }
+        write!(out, "{}{}", &self.prompt_text, self.echoed(&state.line))?;
+        out.flush()?;
+        Ok(state)
+    }
+
+    /// Apply `empty_line` if the submitted command line of `state` is empty.
+    ///
+    /// Returns false if reading goes on instead.
+    pub(crate) fn submit_empty<W: Write>(
+        &mut self,
+        state: &mut LineState,
+        out: &mut W,
+    ) -> Result<bool, Error> {
+        if self.continued.is_some() || !state.line.is_empty() || !state.right_line.is_empty() {
+            return Ok(true);
+        }
+        match (self.empty_line, self.last_entry()) {
+            (EmptyLineBehavior::Ignore, _) => {
+                write!(out, "\n\r")?;
+                *state = self.begin_line(out)?;
+                return Ok(false);
+            }
+            (EmptyLineBehavior::RepeatLast, Some(last)) => {
+                let last = last.clone();
+                self.replace_cmdline(out, &last, &mut state.line, &mut state.right_line)?;
+                state.repeated = true;
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Show the message of the validator if the submitted command line of `state`
+    /// is invalid and return whether it is accepted nevertheless,
+    /// see `Prompt::invalid_line`.
+    pub(crate) fn submit_valid<W: Write>(
+        &self,
+        state: &LineState,
+        out: &mut W,
+    ) -> std::io::Result<bool> {
+        let Some(message) = self.validate(&state.line, &state.right_line) else {
+            return Ok(true);
+        };
+        write!(out, "\n\r{}", message)?;
+        if self.invalid_line == InvalidLineBehavior::Warn {
+            return Ok(true);
+        }
+        writeln!(out)?;
+        self.reprint(out, &state.line, &state.right_line)?;
+        Ok(false)
+    }
+
+    /// Accept the command line of `state`, add it to the history and split it.
+    pub(crate) fn finish_line(&mut self, state: LineState) -> (String, Vec<String>) {
+        self.status_shown = false;
+        let mut line = self.continued.take().unwrap_or_default();
+        line.push_str(&state.line);
+        line.push_str(&state.right_line);
+        self.accept(line, state.repeated)
+    }
+
+    /// Process one key read while prompting for a command line.
+    ///
+    /// Returns whether the command line was submitted.
+    /// If `state.more_keys` is set, typed characters are drawn along with the
+    /// following keys, so pasting text doesn't redraw the line for every character.
+    pub(crate) fn process_key<W: Write>(
+        &mut self,
+        state: &mut LineState,
+        key: std::io::Result<Key>,
+        out: &mut W,
+        terminal: Option<&RawMode>,
+    ) -> Result<bool, Error> {
+        let browsing = (state.history_offset > 0).then(|| {
+            (
+                state.history_offset,
+                format!("{}{}", state.line, state.right_line),
+            )
+        });
+        // A shown suggestion may still be accepted by this key
+        self.typing = self.suggestion_delay.is_some();
+        let submitted = self.handle_key(state, key, out, terminal);
+        self.suggestion_shown = false;
+        let submitted = submitted?;
+        if let Some((offset, text)) = browsing {
+            if state.history_offset == offset
+                && text != format!("{}{}", state.line, state.right_line)
+            {
+                // An edited history entry is a new command line, detached from the history
+                state.history_offset = 0;
+            }
+        }
+        if state.stale && (!state.more_keys || submitted) {
+            state.stale = false;
+            self.redraw(out, &state.line, &state.right_line)?;
+        }
+        Ok(submitted)
+    }
+
+    /// Draw the suggestion held back by `suggestion_delay`, as no key was
+    /// pressed for that long.
+    pub(crate) fn show_suggestion<W: Write>(
+        &mut self,
+        out: &mut W,
+        state: &LineState,
+    ) -> std::io::Result<()> {
+        self.typing = false;
+        self.suggestion_shown = true;
+        if self.suggestion(&state.line, &state.right_line).is_some() {
+            self.redraw(out, &state.line, &state.right_line)?;
+        }
+        Ok(())
+    }
+
+    /// Apply one key to the command line, see `process_key`.
+    fn handle_key<W: Write>(
+        &mut self,
+        state: &mut LineState,
+        key: std::io::Result<Key>,
+        out: &mut W,
+        terminal: Option<&RawMode>,
+    ) -> Result<bool, Error> {
+        // Some terminals send Ctrl+J (line feed) or Ctrl+M (carriage return) for Enter
+        let key = key.map(|key| match key {
+            Ctrl('j' | 'm') if key != self.submit_key && key != self.newline_key => Char('\n'),
+            key => key,
+        });
+        let LineState {
+            line,
+            right_line,
+            history_offset,
+            draft,
+            ctrl_x,
+            quoted_insert,
+            vi,
+            pending_count,
+            completion,
+            more_keys,
+            stale,
+            repeated: _,
+        } = state;
+        let after_completion = match std::mem::take(completion) {
+            CompletionState::Querying(displayed) => {
+                // Answer to the question whether to list all completions
+                if let Ok(Char('y')) = key {
+                    self.list_completions(out, line, right_line, &displayed)?;
+                } else {
+                    write!(out, "\n\r")?;
+                    self.reprint(out, line, right_line)?;
+                }
+                return Ok(false);
+            }
+            CompletionState::Completed => true,
+            CompletionState::Idle => false,
+        };
+        let after_ctrl_x = std::mem::replace(ctrl_x, false);
+        let count = pending_count.take();
+        let key = match key {
+            // A quoted line break is one within the command line
+            Ok(Char('\n')) if std::mem::take(quoted_insert) => Ok(self.newline_key),
+            Ok(key) if std::mem::take(quoted_insert) => {
+                if let Some(ch) = literal_char(key) {
+                    line.push_str(&ch.to_string().repeat(count.unwrap_or(1)));
+                    self.redraw(out, line, right_line)?;
+                }
+                return Ok(false);
+            }
+            key => key,
+        };
+        if let Ok(key) = &key {
+            if let Some(count) = numeric_argument(count, *key) {
+                *pending_count = Some(count);
+                return Ok(false);
+            }
+        }
+        let repeat = count.unwrap_or(1);
+        if let (EditMode::Vi, Ok(key)) = (self.edit_mode, &key) {
+            let command = vi.handle_key(*key);
+            if command != ViCommand::PassThrough {
+                self.apply_vi_command(command, line, right_line);
+                self.redraw(out, line, right_line)?;
+                return Ok(false);
+            }
+        }
+        match key {
+            Ok(key) if key == self.submit_key || key == Ctrl('o') => {
+                if self.suggestion(line, right_line).is_some() {
+                    // Wipe the suggestion, the cursor is in front of it
+                    write!(out, "{}", clear::UntilNewline)?;
+                }
+                line.push_str(right_line);
+                right_line.clear();
+                if EscapingState::process(line).backslash {
+                    // A trailing backslash continues the command line on the next line
+                    line.pop();
+                    self.continue_line(out, &std::mem::take(line))?;
+                    self.reprint(out, line, right_line)?;
+                    return Ok(false);
+                }
+                if key == Ctrl('o') && *history_offset > 0 {
+                    // Offer the following history entry on the next command line
+                    self.next_history = Some(self.history.len() - *history_offset + 1);
+                }
+                return Ok(true);
+            }
+            Ok(key) if key == self.newline_key || key == Char('\n') => {
+                // A line break within the command line
+                let text = format!("{}\n", std::mem::take(line));
+                self.continue_line(out, &text)?;
+                self.reprint(out, line, right_line)?;
+            }
+            Ok(key) if key == self.completion_key => {
+                // The completion key (by default the tabulator) was pressed.
+                *completion = self.completion(out, line, right_line, after_completion)?;
+            }
+            Ok(Char(ch)) => {
+                let room = self.max_line_len.map_or(repeat, |max| {
+                    let continued = self.continued.as_deref().unwrap_or_default();
+                    let len = [continued, line, right_line]
+                        .iter()
+                        .map(|text| text.chars().count())
+                        .sum::<usize>();
+                    max.saturating_sub(len).min(repeat)
+                });
+                if room == 0 {
+                    self.ring_bell(out)?;
+                    return Ok(false);
+                }
+                let inserted = ch.to_string().repeat(room);
+                line.push_str(&inserted);
+                if *more_keys || *stale {
+                    // Drawn by `process_key` once no more keys are available
+                    *stale = true;
+                } else if self.can_redraw_tail() {
+                    self.redraw_tail(out, "", &inserted, right_line)?
+                } else {
+                    self.redraw(out, line, right_line)?
+                }
+            }
+            Ok(Key::Left) => {
+                let mut moved = String::new();
+                while moved.chars().count() < repeat {
+                    match line.pop() {
+                        Some(ch) => {
+                            right_line.insert(0, ch);
+                            moved.insert(0, ch);
+                        }
+                        None => break,
+                    }
+                }
+                let moved = visible_width(&self.echoed(&moved));
+                if moved > 0 {
+                    write!(out, "{}", cursor::Left(moved as u16))?;
+                    out.flush()?
+                }
+            }
+            Ok(Key::Right) => {
+                if !right_line.is_empty() {
+                    let mut moved = String::new();
+                    while moved.chars().count() < repeat && !right_line.is_empty() {
+                        let ch = right_line.remove(0);
+                        line.push(ch);
+                        moved.push(ch);
+                    }
+                    let moved = visible_width(&self.echoed(&moved));
+                    if moved > 0 {
+                        write!(out, "{}", cursor::Right(moved as u16))?;
+                        out.flush()?
+                    }
+                } else if self.suggestion(line, right_line).is_some() {
+                    self.accept_suggestion(line, right_line);
+                    self.redraw(out, line, right_line)?;
+                }
+            }
+            Ok(Ctrl('x')) => *ctrl_x = true,
+            Ok(Ctrl('v')) => *quoted_insert = true,
+            Ok(Ctrl('e')) if after_ctrl_x => {
+                self.edit_in_editor(out, terminal, line, right_line)?
+            }
+            Ok(Key::Home) => {
+                *right_line = format!("{}{}", line, right_line);
+                *line = String::new();
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Key::End) | Ok(Ctrl('e')) => {
+                line.push_str(right_line);
+                *right_line = String::new();
+                self.accept_suggestion(line, right_line);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Key::Up | Key::Down) if !self.history_enabled => {}
+            Ok(Key::Up) => {
+                if *history_offset == 0 {
+                    // Keep the command line being typed to restore it later
+                    let continued = self.continued.as_deref().unwrap_or_default();
+                    *draft = format!("{}{}{}", continued, line, right_line);
+                }
+                // Go back to the next older entry starting with the typed text
+                let older = (*history_offset + 1..=self.history.len())
+                    .find(|offset| self.history[self.history.len() - offset].starts_with(&*draft));
+                if let Some(offset) = older {
+                    *history_offset = offset;
+                    let new_cmd_line = self.history[self.history.len() - offset].clone();
+                    self.replace_cmdline(out, &new_cmd_line, line, right_line)?;
+                }
+            }
+            // Not browsing the history, so there is nothing newer
+            Ok(Key::Down) if *history_offset == 0 => {}
+            Ok(Key::Down) => {
+                let newer = (1..*history_offset)
+                    .rev()
+                    .find(|offset| self.history[self.history.len() - offset].starts_with(&*draft));
+                if let Some(offset) = newer {
+                    *history_offset = offset;
+                    let new_cmd_line = self.history[self.history.len() - offset].clone();
+                    self.replace_cmdline(out, &new_cmd_line, line, right_line)?;
+                } else {
+                    // Back at the command line being typed
+                    *history_offset = 0;
+                    let draft = std::mem::take(draft);
+                    self.replace_cmdline(out, &draft, line, right_line)?;
+                }
+            }
+            Ok(Ctrl('k')) => {
+                self.kill_to_end(right_line);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Ctrl('u')) => {
+                self.kill_to_start(line);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Ctrl('w')) => {
+                self.kill_word_backward(line, repeat);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Alt('d')) => {
+                self.kill_word_forward(line, right_line, repeat);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Alt('u')) => {
+                transform_word(line, right_line, str::to_uppercase);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Alt('l')) => {
+                transform_word(line, right_line, str::to_lowercase);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Alt('c')) => {
+                transform_word(line, right_line, capitalize);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Ctrl('y')) => {
+                self.yank(line);
+                self.redraw(out, line, right_line)?;
+            }
+            Ok(Ctrl('c')) => {
+                let continued = self.continued.as_deref().unwrap_or_default();
+                self.cancelled = Some(format!("{}{}{}", continued, line, right_line));
+                return Err(Error::CtrlC);
+            }
+            Ok(Ctrl('d'))
+                if line.is_empty() && right_line.is_empty() && self.continued.is_none() =>
+            {
+                return Err(Error::CtrlD)
+            }
+            Ok(Ctrl('d')) => match self.ctrl_d_action {
+                CtrlDAction::Ignore => {}
+                CtrlDAction::DeleteChar => self.delete_forward(out, line, right_line, repeat)?,
+            },
+            Ok(Key::Backspace) => {
+                // Remove whole grapheme clusters, like an emoji with a skin tone modifier
+                let start = line
+                    .grapheme_indices(true)
+                    .rev()
+                    .take(repeat)
+                    .last()
+                    .map_or(line.len(), |(pos, _)| pos);
+                let erased = line.split_off(start);
+                // Terminals disagree on the width of multi-character clusters
+                let simple = erased.graphemes(true).count() == erased.chars().count();
+                if !erased.is_empty() && simple && self.can_redraw_tail() {
+                    self.redraw_tail(out, &erased, "", right_line)?;
+                } else if !erased.is_empty() {
+                    self.redraw(out, line, right_line)?;
+                }
+            }
+            Ok(Key::Delete) => self.delete_forward(out, line, right_line, repeat)?,
+            Ok(Alt('\u{7f}')) => {
+                // ALT+← was pressed.
+                // Remove the last component, leaving the rest of the line as it is.
+                let old_len = line.len();
+                for _ in 0..repeat {
+                    line.truncate(prev_word_boundary(line, line.len()));
+                }
+                if line.len() < old_len {
+                    self.redraw(out, line, right_line)?;
+                }
+            }
+            Ok(key @ (Key::Insert | Key::PageUp | Key::PageDown | Key::F(_))) => {
+                let action = self
+                    .special_key_handler
+                    .as_mut()
+                    .and_then(|handler| handler(key));
+                match action {
+                    Some(Action::Insert(text)) => {
+                        line.push_str(&text);
+                        self.redraw(out, line, right_line)?;
+                    }
+                    Some(Action::Replace(text)) => {
+                        *history_offset = 0;
+                        self.replace_cmdline(out, &text, line, right_line)?;
+                    }
+                    Some(Action::Print(text)) => {
+                        write!(out, "\n\r{}\n", text)?;
+                        self.reprint(out, line, right_line)?;
+                    }
+                    Some(Action::HistoryStart) if self.last_entry().is_some() => {
+                        if *history_offset == 0 {
+                            let continued = self.continued.as_deref().unwrap_or_default();
+                            *draft = format!("{}{}{}", continued, line, right_line);
+                        }
+                        *history_offset = self.history.len();
+                        let oldest = self.history[0].clone();
+                        self.replace_cmdline(out, &oldest, line, right_line)?;
+                    }
+                    Some(Action::HistoryStart) | None => {}
+                }
+            }
+            Ok(_) => {}
+            Err(e) => return Err(Error::IoError(e)),
+        }
+        Ok(false)
+    }
+}
+
+/// Builder for a `Prompt` with options, see `Prompt::builder`.
+///
+/// Every option defaults to what `Prompt::new` sets up, so only the
+/// differing ones need to be given.
+pub struct PromptBuilder {
+    prompt: Prompt,
+}
+
+impl PromptBuilder {
+    /// The text written before the user input, empty by default
+    pub fn prompt_text(mut self, prompt_text: impl Into<String>) -> PromptBuilder {
+        self.prompt.set_prompt(prompt_text);
+        self
+    }
+
+    /// The available commands used by tab completion
+    pub fn commands(mut self, commands: Vec<Command>) -> PromptBuilder {
+        self.prompt.commands = commands;
+        self
+    }
+
+    /// Add `command` to the available commands
+    pub fn command(mut self, command: Command) -> PromptBuilder {
+        self.prompt.commands.push(command);
+        self
+    }
+
+    /// Start with `history`, ordered from oldest to newest, see `Prompt::set_history`
+    pub fn history(mut self, history: Vec<String>) -> PromptBuilder {
+        self.prompt.set_history(history);
+        self
+    }
+
+    /// See `Prompt::autosuggest`
+    pub fn autosuggest(mut self, autosuggest: bool) -> PromptBuilder {
+        self.prompt.autosuggest = autosuggest;
+        self
+    }
+
+    pub fn edit_mode(mut self, edit_mode: EditMode) -> PromptBuilder {
+        self.prompt.edit_mode = edit_mode;
+        self
+    }
+
+    /// See `Prompt::bell_style`
+    pub fn bell_style(mut self, bell_style: BellStyle) -> PromptBuilder {
+        self.prompt.bell_style = bell_style;
+        self
+    }
+
+    /// See `Prompt::history_enabled`
+    pub fn history_enabled(mut self, history_enabled: bool) -> PromptBuilder {
+        self.prompt.history_enabled = history_enabled;
+        self
+    }
+
+    /// See `Prompt::trim_on_submit`
+    pub fn trim_on_submit(mut self, trim_on_submit: bool) -> PromptBuilder {
+        self.prompt.trim_on_submit = trim_on_submit;
+        self
+    }
+
+    /// See `Prompt::max_line_len`
+    pub fn max_line_len(mut self, max_line_len: usize) -> PromptBuilder {
+        self.prompt.max_line_len = Some(max_line_len);
+        self
+    }
+
+    /// See `Prompt::empty_line`
+    pub fn empty_line(mut self, empty_line: EmptyLineBehavior) -> PromptBuilder {
+        self.prompt.empty_line = empty_line;
+        self
+    }
+
+    /// See `Prompt::invalid_line`
+    pub fn invalid_line(mut self, invalid_line: InvalidLineBehavior) -> PromptBuilder {
+        self.prompt.invalid_line = invalid_line;
+        self
+    }
+
+    /// See `Prompt::comment_marker`
+    pub fn comment_marker(mut self, marker: impl Into<String>) -> PromptBuilder {
+        self.prompt.comment_marker = Some(marker.into());
+        self
+    }
+
+    /// See `Prompt::ctrl_d_action`
+    pub fn ctrl_d_action(mut self, ctrl_d_action: CtrlDAction) -> PromptBuilder {
+        self.prompt.ctrl_d_action = ctrl_d_action;
+        self
+    }
+
+    /// See `Prompt::complete_prefix_first`
+    pub fn complete_prefix_first(mut self, complete_prefix_first: bool) -> PromptBuilder {
+        self.prompt.complete_prefix_first = complete_prefix_first;
+        self
+    }
+
+    /// See `Prompt::catch_completion_panics`
+    pub fn catch_completion_panics(mut self, catch_completion_panics: bool) -> PromptBuilder {
+        self.prompt.catch_completion_panics = catch_completion_panics;
+        self
+    }
+
+    /// See `Prompt::sort_completions`
+    pub fn sort_completions(mut self, sort_completions: bool) -> PromptBuilder {
+        self.prompt.sort_completions = sort_completions;
+        self
+    }
+
+    /// See `Prompt::rank_completions`
+    pub fn rank_completions(mut self, rank_completions: bool) -> PromptBuilder {
+        self.prompt.rank_completions = rank_completions;
+        self
+    }
+
+    /// See `Prompt::completion_query_items`
+    pub fn completion_query_items(mut self, items: Option<usize>) -> PromptBuilder {
+        self.prompt.completion_query_items = items;
+        self
+    }
+
+    /// See `Prompt::continuation_prompt`
+    pub fn continuation_prompt(mut self, continuation_prompt: impl Into<String>) -> PromptBuilder {
+        self.prompt.continuation_prompt = continuation_prompt.into();
+        self
+    }
+
+    /// See `Prompt::raise_sigint`
+    pub fn raise_sigint(mut self, raise_sigint: bool) -> PromptBuilder {
+        self.prompt.raise_sigint = raise_sigint;
+        self
+    }
+
+    /// See `Prompt::with_validator`
+    pub fn validator(mut self, validator: impl Fn(&str) -> Validation + 'static) -> PromptBuilder {
+        self.prompt = self.prompt.with_validator(validator);
+        self
+    }
+
+    /// See `Prompt::with_highlighter`
+    pub fn highlighter(mut self, highlighter: impl Fn(&str) -> String + 'static) -> PromptBuilder {
+        self.prompt = self.prompt.with_highlighter(highlighter);
+        self
+    }
+
+    /// See `Prompt::with_echo`
+    pub fn echo(mut self, echo: impl Fn(char) -> Option<String> + 'static) -> PromptBuilder {
+        self.prompt = self.prompt.with_echo(echo);
+        self
+    }
+
+    /// See `Prompt::with_special_key_handler`
+    pub fn special_key_handler(
+        mut self,
+        handler: impl FnMut(Key) -> Option<Action> + 'static,
+    ) -> PromptBuilder {
+        self.prompt = self.prompt.with_special_key_handler(handler);
+        self
+    }
+
+    /// See `Prompt::with_line_callback`
+    pub fn line_callback(mut self, callback: impl FnMut(&str) + 'static) -> PromptBuilder {
+        self.prompt = self.prompt.with_line_callback(callback);
+        self
+    }
+
+    /// See `Prompt::with_postprocessor`
+    pub fn postprocessor(
+        mut self,
+        postprocessor: impl Fn(Vec<String>) -> Vec<String> + 'static,
+    ) -> PromptBuilder {
+        self.prompt = self.prompt.with_postprocessor(postprocessor);
+        self
+    }
+
+    /// See `Prompt::with_completion_key`
+    pub fn completion_key(mut self, key: Key) -> PromptBuilder {
+        self.prompt = self.prompt.with_completion_key(key);
+        self
+    }
+
+    /// See `Prompt::with_submit_key`
+    pub fn submit_key(mut self, key: Key) -> PromptBuilder {
+        self.prompt = self.prompt.with_submit_key(key);
+        self
+    }
+
+    /// See `Prompt::with_newline_key`
+    pub fn newline_key(mut self, key: Key) -> PromptBuilder {
+        self.prompt = self.prompt.with_newline_key(key);
+        self
+    }
+
+    /// See `Prompt::with_completion_renderer`
+    pub fn completion_renderer(
+        mut self,
+        renderer: impl CompletionRenderer + 'static,
+    ) -> PromptBuilder {
+        self.prompt = self.prompt.with_completion_renderer(renderer);
+        self
+    }
+
+    /// Create the `Prompt` with the given options
+    pub fn build(self) -> Prompt {
+        self.prompt
     }
 }
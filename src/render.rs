@@ -0,0 +1,95 @@
+//! Presentation of completions below the command line.
+
+use crate::completion::CompletionResult;
+
+/// Renders what tab completion found, see `Prompt::with_completion_renderer`.
+///
+/// `render` receives the completions to show, which are either a
+/// `Description`, a `PossibilityList` or a `DescribedList`, and the terminal
+/// width in columns. It returns the bytes to write below the command line,
+/// each line starting with `\n\r`, as the terminal is in raw mode.
+/// The command line is printed again afterwards.
+pub trait CompletionRenderer {
+    fn render(&self, result: &CompletionResult, width: u16) -> Vec<u8>;
+}
+
+/// Lists completions in a single line, or one per line if they are described
+///
+/// ```
+/// use shli::completion::CompletionResult;
+/// use shli::render::{CompletionRenderer, DefaultRenderer};
+///
+/// let result = CompletionResult::PossibilityList(vec!["cat".to_string(), "cd".to_string()]);
+/// assert_eq!(DefaultRenderer.render(&result, 80), b"\n\r Completions: [\"cat\", \"cd\"]");
+/// ```
+pub struct DefaultRenderer;
+
+impl CompletionRenderer for DefaultRenderer {
+    fn render(&self, result: &CompletionResult, _width: u16) -> Vec<u8> {
+        match result {
+            CompletionResult::None => String::new(),
+            CompletionResult::Description(description) => {
+                format!("\n\r Parameter help: {}", description)
+            }
+            CompletionResult::PossibilityList(words) => format!("\n\r Completions: {:?}", words),
+            CompletionResult::DescribedList(list) => {
+                // One line per possibility, with the descriptions in a column
+                let width = list
+                    .iter()
+                    .map(|(word, _)| word.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let mut text = String::from("\n\r Completions:");
+                for (word, description) in list {
+                    text.push_str(&format!("\n\r   {:width$}  {}", word, description));
+                }
+                text
+            }
+        }
+        .into_bytes()
+    }
+}
+
+/// Lays out undescribed completions in columns fitting the terminal width,
+/// sorted top to bottom like `ls` does. Anything else is rendered like by
+/// `DefaultRenderer`.
+///
+/// ```
+/// use shli::completion::CompletionResult;
+/// use shli::render::{ColumnRenderer, CompletionRenderer};
+///
+/// let words = ["a", "b", "c"].map(String::from).to_vec();
+/// let result = CompletionResult::PossibilityList(words);
+/// assert_eq!(ColumnRenderer.render(&result, 6), b"\n\ra  c\n\rb");
+/// ```
+pub struct ColumnRenderer;
+
+impl CompletionRenderer for ColumnRenderer {
+    fn render(&self, result: &CompletionResult, width: u16) -> Vec<u8> {
+        let words = match result {
+            CompletionResult::PossibilityList(words) if !words.is_empty() => words,
+            _ => return DefaultRenderer.render(result, width),
+        };
+        let column_width = words
+            .iter()
+            .map(|word| word.chars().count())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let columns = (width as usize / column_width).max(1);
+        let rows = words.len().div_ceil(columns);
+        let mut text = String::new();
+        for row in 0..rows {
+            text.push_str("\n\r");
+            let mut cells = words.iter().skip(row).step_by(rows).peekable();
+            while let Some(word) = cells.next() {
+                if cells.peek().is_some() {
+                    text.push_str(&format!("{:column_width$}", word));
+                } else {
+                    text.push_str(word);
+                }
+            }
+        }
+        text.into_bytes()
+    }
+}
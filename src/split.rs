@@ -1,3 +1,30 @@
+//! Parsing of command lines into their components.
+//!
+//! `split` splits a command line (`try_split` if it may be unfinished, `split_iter`
+//! without collecting the components), while `quote` and `join` build one.
+//! `EscapingState` is the state machine behind them, which may be used to
+//! build other tokenizers. All of these are re-exported at the crate root.
+
+use std::borrow::Cow;
+use std::str::CharIndices;
+
+/// How a character of a command line is treated, see `EscapingState::step_classify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    /// Content of a component
+    Literal,
+    /// Unescaped whitespace separating components
+    Delimiter,
+    /// A quote starting a quoted sequence
+    OpenQuote,
+    /// A quote ending a quoted sequence
+    CloseQuote,
+    /// A backslash escaping the following character
+    EscapeStart,
+    /// Content of a component, escaped by the preceding backslash
+    Escaped,
+}
+
 /// Simple state machine for processing escaping within command line strings.
 ///
 /// An example usage:
@@ -21,6 +48,11 @@ pub struct EscapingState {
     pub double_quote: bool,
     /// A backslash is active.
     pub backslash: bool,
+    /// Characters quoting like `'` in addition to `'` and `"`, e.g. a backtick.
+    /// Empty by default, see `with_quotes`.
+    quotes: Vec<char>,
+    /// The character of `quotes` that started the active quoted sequence, if any
+    custom_quote: Option<char>,
 }
 
 impl EscapingState {
@@ -29,50 +61,108 @@ impl EscapingState {
             single_quote: false,
             double_quote: false,
             backslash: false,
+            quotes: vec![],
+            custom_quote: None,
+        }
+    }
+
+    /// A state treating `quotes` as quote characters too, in addition to `'` and `"`.
+    ///
+    /// Within them, everything is escaped like within `'…'`.
+    ///
+    /// ```
+    /// use shli::split::{CharClass, EscapingState};
+    ///
+    /// let mut state = EscapingState::with_quotes(&['`']);
+    /// assert_eq!(state.step_classify('`'), CharClass::OpenQuote);
+    /// assert!(state.whitespace_escaped());
+    /// assert_eq!(state.step_classify('`'), CharClass::CloseQuote);
+    /// ```
+    pub fn with_quotes(quotes: &[char]) -> EscapingState {
+        EscapingState {
+            quotes: quotes.to_vec(),
+            ..EscapingState::new()
         }
     }
 
     /// Call this function to proceed on the input string.
     pub fn step(&mut self, ch: char) {
-        match ch {
-            '"' => {
-                if !self.doublequote_escaped() {
-                    self.double_quote = !self.double_quote
+        self.step_classify(ch);
+    }
+
+    /// Proceed on the input string like `step`, and return how `ch` was treated.
+    ///
+    /// ```
+    /// use shli::split::{CharClass, EscapingState};
+    ///
+    /// let mut state = EscapingState::new();
+    /// assert_eq!(state.step_classify('"'), CharClass::OpenQuote);
+    /// assert_eq!(state.step_classify(' '), CharClass::Literal);
+    /// ```
+    pub fn step_classify(&mut self, ch: char) -> CharClass {
+        self.step_classify_with(ch, char::is_whitespace)
+    }
+
+    /// Like `step_classify`, with `is_delimiter` telling which characters
+    /// delimit components instead of whitespace.
+    fn step_classify_with(&mut self, ch: char, is_delimiter: impl Fn(char) -> bool) -> CharClass {
+        let class = match ch {
+            ch if self.custom_quote == Some(ch) && !self.backslash => {
+                self.custom_quote = None;
+                CharClass::CloseQuote
+            }
+            ch if self.quotes.contains(&ch) && !self.whitespace_escaped() => {
+                self.custom_quote = Some(ch);
+                CharClass::OpenQuote
+            }
+            '"' if !self.doublequote_escaped() => {
+                self.double_quote = !self.double_quote;
+                if self.double_quote {
+                    CharClass::OpenQuote
+                } else {
+                    CharClass::CloseQuote
                 }
             }
-            '\'' => {
-                if !self.singlequote_escaped() {
-                    self.single_quote = !self.single_quote
+            '\'' if !self.singlequote_escaped() => {
+                self.single_quote = !self.single_quote;
+                if self.single_quote {
+                    CharClass::OpenQuote
+                } else {
+                    CharClass::CloseQuote
                 }
             }
-            _ => {}
-        }
+            '\\' if !self.backslash_escaped() => CharClass::EscapeStart,
+            ch if is_delimiter(ch) && !self.whitespace_escaped() => CharClass::Delimiter,
+            _ if self.backslash => CharClass::Escaped,
+            _ => CharClass::Literal,
+        };
 
         if self.backslash {
             self.backslash = false
         } else if ch == '\\' {
             self.backslash = true
         }
+        class
     }
 
     /// If the next character would be whitespace, would it be escaped
     /// or viewed as a component delimiter?
     pub fn whitespace_escaped(&self) -> bool {
-        self.single_quote || self.double_quote || self.backslash
+        self.single_quote || self.double_quote || self.backslash || self.custom_quote.is_some()
     }
 
     /// If the next character would be `"`, would it be escaped
     /// or would it start/end a string sequence, in which whitespace and single
     /// quote are escaped? (`"A B C \""`)
     pub fn doublequote_escaped(&self) -> bool {
-        self.single_quote || self.backslash
+        self.single_quote || self.backslash || self.custom_quote.is_some()
     }
 
     /// If the next character would be `'`, would it be escaped
     /// or would it start/end a string sequence, in which whitespace and double
     /// quotes are escaped? (`'A B C \''`)
     pub fn singlequote_escaped(&self) -> bool {
-        self.double_quote || self.backslash
+        self.double_quote || self.backslash || self.custom_quote.is_some()
     }
 
     /// If the next character would be a backslash (`\`), would it
@@ -99,47 +189,353 @@ impl EscapingState {
 /// The main difference to `split_whitespace` is:
 /// It respects whitespace escaping (`"`, `'`, `\`) as well as escaping
 /// of the escaping characters (`\"`, `'\'`, …).
-/// Thus, strings (`"A B C"`) will show up as single arguments.
+/// Thus, strings (`"A B C"`) will show up as single arguments, and an empty
+/// string (`''`) as an empty argument.
+/// Adjacent quoted and unquoted parts form a single argument, like in a shell:
+/// ```
+/// use shli::split::split;
+///
+/// assert_eq!(split("'a'b\"c\" pre\"fix mid\"suf"), vec!["abc", "prefix midsuf"]);
+/// ```
 pub fn split(cmdline: &str) -> Vec<String> {
-    let mut parts = vec![];
-    let mut act = String::new();
-    let mut state = EscapingState::new();
-    for ch in cmdline.chars() {
-        if !state.whitespace_escaped() && ch.is_whitespace() {
-            if !act.is_empty() {
-                parts.push(act);
-                act = String::new();
-            }
-            continue;
+    split_with(cmdline, char::is_whitespace)
+}
+
+/// Splits a commandline like `split`, but only at the characters for which
+/// `is_delimiter` returns true, instead of at any whitespace.
+///
+/// ```
+/// use shli::split::split_with;
+///
+/// let words = split_with("mv a\u{a0}b c", |ch| ch == ' ' || ch == '\t');
+/// assert_eq!(words, vec!["mv", "a\u{a0}b", "c"]);
+/// ```
+pub fn split_with(cmdline: &str, is_delimiter: impl Fn(char) -> bool) -> Vec<String> {
+    Components::new(cmdline, is_delimiter)
+        .map(Cow::into_owned)
+        .collect()
+}
+
+/// Splits a commandline like `split`, with `quotes` quoting like `'` in
+/// addition to `'` and `"`, see `EscapingState::with_quotes`.
+///
+/// ```
+/// use shli::split::split_with_quotes;
+///
+/// let words = split_with_quotes("eval `a b` 'c d'", &['`']);
+/// assert_eq!(words, vec!["eval", "a b", "c d"]);
+/// ```
+pub fn split_with_quotes(cmdline: &str, quotes: &[char]) -> Vec<String> {
+    let mut components = Components::new(cmdline, char::is_whitespace);
+    components.state = EscapingState::with_quotes(quotes);
+    components.map(Cow::into_owned).collect()
+}
+
+/// Splits a commandline like `split`, but yields the components one by one
+/// instead of collecting them.
+///
+/// Components without quotes and backslashes are borrowed from `cmdline`,
+/// only the others are allocated.
+///
+/// ```
+/// use shli::split::split_iter;
+/// use std::borrow::Cow;
+///
+/// let mut components = split_iter("cat 'a b' c");
+/// assert_eq!(components.next(), Some(Cow::Borrowed("cat")));
+/// assert_eq!(components.next(), Some(Cow::Owned("a b".to_string())));
+/// assert_eq!(components.next(), Some(Cow::Borrowed("c")));
+/// assert_eq!(components.next(), None);
+/// ```
+pub fn split_iter(cmdline: &str) -> impl Iterator<Item = Cow<'_, str>> + '_ {
+    Components::new(cmdline, char::is_whitespace)
+}
+
+/// Splits a commandline like `split`, into the components before and after the
+/// first standalone `--`, which itself is dropped.
+///
+/// In POSIX command lines, `--` ends the flags, so the components after it
+/// are passed on as they are, e.g. to a wrapped program. A quoted `'--'` is
+/// an ordinary component.
+///
+/// ```
+/// use shli::split::split_passthrough;
+///
+/// let (own, passed) = split_passthrough("exec -v -- ls -l '--'");
+/// assert_eq!(own, vec!["exec", "-v"]);
+/// assert_eq!(passed, vec!["ls", "-l", "--"]);
+/// ```
+pub fn split_passthrough(cmdline: &str) -> (Vec<String>, Vec<String>) {
+    let mut components = split_iter(cmdline);
+    let own = components
+        .by_ref()
+        .take_while(|component| !matches!(component, Cow::Borrowed("--")))
+        .map(Cow::into_owned)
+        .collect();
+    (own, components.map(Cow::into_owned).collect())
+}
+
+/// Iterator over the components of a command line, see `split_iter`
+struct Components<'a, F> {
+    cmdline: &'a str,
+    chars: CharIndices<'a>,
+    state: EscapingState,
+    is_delimiter: F,
+}
+
+impl<'a, F: Fn(char) -> bool> Components<'a, F> {
+    fn new(cmdline: &'a str, is_delimiter: F) -> Self {
+        Components {
+            cmdline,
+            chars: cmdline.char_indices(),
+            state: EscapingState::new(),
+            is_delimiter,
         }
+    }
+}
 
-        match ch {
-            '"' => {
-                if state.doublequote_escaped() {
-                    act.push(ch);
+impl<'a, F: Fn(char) -> bool> Iterator for Components<'a, F> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        // Byte range of the current component while it can be borrowed
+        let mut range: Option<(usize, usize)> = None;
+        // The current component once quotes or backslashes were removed from it
+        let mut owned: Option<String> = None;
+        for (pos, ch) in self.chars.by_ref() {
+            match self.state.step_classify_with(ch, &self.is_delimiter) {
+                CharClass::Literal | CharClass::Escaped => match (&mut owned, &mut range) {
+                    (Some(owned), _) => owned.push(ch),
+                    (None, Some((_, end))) => *end = pos + ch.len_utf8(),
+                    (None, None) => range = Some((pos, pos + ch.len_utf8())),
+                },
+                CharClass::Delimiter if owned.is_some() || range.is_some() => break,
+                CharClass::Delimiter => {}
+                CharClass::OpenQuote | CharClass::CloseQuote => {
+                    // Even an empty quoted component exists
+                    owned.get_or_insert_with(|| match range {
+                        Some((start, end)) => self.cmdline[start..end].to_string(),
+                        None => String::new(),
+                    });
                 }
-            }
-            '\'' => {
-                if state.singlequote_escaped() {
-                    act.push(ch);
+                // The backslash is dropped, so the text before it can't be borrowed.
+                // Only the escaped character starts a component, not the backslash.
+                CharClass::EscapeStart => {
+                    if let (None, Some((start, end))) = (&owned, range) {
+                        owned = Some(self.cmdline[start..end].to_string());
+                    }
                 }
             }
-            '\\' => {
-                if state.backslash_escaped() {
-                    act.push(ch);
-                }
+        }
+        match (owned, range) {
+            (Some(owned), _) => Some(Cow::Owned(owned)),
+            (None, Some((start, end))) => Some(Cow::Borrowed(&self.cmdline[start..end])),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Splits a command line fed character by character, e.g. as it arrives in
+/// chunks from a stream, into the components `split` returns.
+///
+/// ```
+/// use shli::split::Tokenizer;
+///
+/// let mut tokenizer = Tokenizer::new();
+/// let mut words: Vec<String> = "echo 'a b'".chars().filter_map(|ch| tokenizer.push(ch)).collect();
+/// assert_eq!(words, vec!["echo"]);
+/// assert!(!tokenizer.state().whitespace_escaped());
+/// words.extend(tokenizer.finish());
+/// assert_eq!(words, vec!["echo", "a b"]);
+/// ```
+#[derive(Debug, Default)]
+pub struct Tokenizer {
+    state: EscapingState,
+    /// The component being read, `None` between components
+    token: Option<String>,
+}
+
+impl Tokenizer {
+    pub fn new() -> Tokenizer {
+        Tokenizer::with_state(EscapingState::new())
+    }
+
+    /// A tokenizer starting in `state`, e.g. `EscapingState::with_quotes`
+    pub fn with_state(state: EscapingState) -> Tokenizer {
+        Tokenizer { state, token: None }
+    }
+
+    /// Feed the next character, returning the component it completes, if any.
+    ///
+    /// A component is completed by the unescaped whitespace following it.
+    pub fn push(&mut self, ch: char) -> Option<String> {
+        match self.state.step_classify(ch) {
+            CharClass::Literal | CharClass::Escaped => {
+                self.token.get_or_insert_with(String::new).push(ch)
             }
-            ch => act.push(ch),
+            CharClass::Delimiter => return self.token.take(),
+            // Even an empty quoted component exists
+            CharClass::OpenQuote | CharClass::CloseQuote => {
+                self.token.get_or_insert_with(String::new);
+            }
+            // Only the escaped character starts a component
+            CharClass::EscapeStart => {}
         }
-        state.step(ch);
+        None
+    }
+
+    /// The escaping state after the characters fed so far, which tells e.g.
+    /// whether a quote is open
+    pub fn state(&self) -> &EscapingState {
+        &self.state
+    }
+
+    /// The component being read, as far as it was fed yet
+    pub fn current(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Complete the input, returning the last component if it isn't followed
+    /// by whitespace.
+    pub fn finish(self) -> Option<String> {
+        self.token
     }
+}
 
-    if !act.is_empty() {
-        parts.push(act);
+/// Splits a commandline like `split`, but fails if it is unfinished, i.e. if a
+/// quote isn't closed or it ends with a backslash.
+///
+/// ```
+/// use shli::split::{try_split, ParseError};
+///
+/// assert_eq!(try_split("echo 'a b'"), Ok(vec!["echo".to_string(), "a b".to_string()]));
+/// assert_eq!(try_split("echo 'a b"), Err(ParseError::UnterminatedSingleQuote { pos: 5 }));
+/// ```
+pub fn try_split(cmdline: &str) -> Result<Vec<String>, ParseError> {
+    let mut state = EscapingState::new();
+    // Byte positions of the last opening quote and escaping backslash
+    let mut quote_start = 0;
+    let mut escape_start = 0;
+    for (pos, ch) in cmdline.char_indices() {
+        match state.step_classify(ch) {
+            CharClass::OpenQuote => quote_start = pos,
+            CharClass::EscapeStart => escape_start = pos,
+            _ => {}
+        }
+    }
+    if state.single_quote {
+        Err(ParseError::UnterminatedSingleQuote { pos: quote_start })
+    } else if state.double_quote {
+        Err(ParseError::UnterminatedDoubleQuote { pos: quote_start })
+    } else if state.backslash {
+        Err(ParseError::TrailingBackslash { pos: escape_start })
+    } else {
+        Ok(split(cmdline))
     }
-    parts
 }
 
+/// Why a command line couldn't be parsed, see `try_split`.
+///
+/// `pos` is the byte position of the offending character in the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The single quote at `pos` isn't closed
+    UnterminatedSingleQuote { pos: usize },
+    /// The double quote at `pos` isn't closed
+    UnterminatedDoubleQuote { pos: usize },
+    /// The command line ends with the backslash at `pos`, which escapes nothing
+    TrailingBackslash { pos: usize },
+    /// An operator at `pos` is not allowed there, for tokenizers knowing operators
+    UnexpectedOperator { pos: usize },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::UnterminatedSingleQuote { pos } => {
+                write!(f, "unterminated single quote at position {}", pos)
+            }
+            ParseError::UnterminatedDoubleQuote { pos } => {
+                write!(f, "unterminated double quote at position {}", pos)
+            }
+            ParseError::TrailingBackslash { pos } => {
+                write!(f, "trailing backslash at position {}", pos)
+            }
+            ParseError::UnexpectedOperator { pos } => {
+                write!(f, "unexpected operator at position {}", pos)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Cuts off the comment starting at the first `marker` which is neither
+/// quoted nor escaped.
+///
+/// Like in a shell, the marker only starts a comment at the beginning of a
+/// component, so it may be part of a word.
+///
+/// ```
+/// use shli::split::strip_comment;
+///
+/// assert_eq!(strip_comment("ls -l # details", "#"), "ls -l ");
+/// assert_eq!(strip_comment("echo '#1' \\#2", "#"), "echo '#1' \\#2");
+/// assert_eq!(strip_comment("git log --format=#%h", "#"), "git log --format=#%h");
+/// ```
+pub fn strip_comment<'a>(cmdline: &'a str, marker: &str) -> &'a str {
+    if marker.is_empty() {
+        return cmdline;
+    }
+    let mut state = EscapingState::new();
+    let mut word_start = true;
+    for (pos, ch) in cmdline.char_indices() {
+        if word_start && cmdline[pos..].starts_with(marker) {
+            return &cmdline[..pos];
+        }
+        word_start = state.step_classify(ch) == CharClass::Delimiter;
+    }
+    cmdline
+}
+
+/// Escapes `word` so that `split` yields it as a single component again.
+///
+/// Whitespace, quotes and backslashes are escaped by a backslash.
+/// ```
+/// use shli::split::{quote, split};
+///
+/// assert_eq!(quote("my file.txt"), "my\\ file.txt");
+/// assert_eq!(split(&quote("it's \"x\"")), vec!["it's \"x\""]);
+/// ```
+pub fn quote(word: &str) -> String {
+    if word.is_empty() {
+        return String::from("''");
+    }
+    let mut quoted = String::with_capacity(word.len());
+    for ch in word.chars() {
+        if ch.is_whitespace() || matches!(ch, '"' | '\'' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted
+}
+
+/// Joins `words` into a command line, which `split` turns into `words` again.
+///
+/// This is the inverse of `split`.
+pub fn join<S: AsRef<str>>(words: &[S]) -> String {
+    words
+        .iter()
+        .map(|word| quote(word.as_ref()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether the last character of `text` is whitespace.
+///
+/// After such a command line, the user begins typing a new component.
+/// Note that this doesn't consider escaping, e.g. of `a\ `.
 pub fn ends_with_whitespace(text: &str) -> bool {
     if let Some(ch) = text.chars().last() {
         ch.is_whitespace()
@@ -0,0 +1,337 @@
+//! Thin abstraction over the terminal backend.
+//!
+//! By default, termion is used. With the `crossterm` feature (and default
+//! features disabled), crossterm is used instead, which also supports Windows.
+//! The rest of the crate only uses the items of this module.
+
+#[cfg(feature = "termion")]
+pub use self::termion_backend::*;
+
+#[cfg(all(feature = "crossterm", not(feature = "termion")))]
+pub use self::crossterm_backend::*;
+
+#[cfg(not(any(feature = "termion", feature = "crossterm")))]
+compile_error!("shli needs either the `termion` or the `crossterm` feature");
+
+/// Keeps the terminal in raw mode while it lives, for doing own low-level
+/// terminal I/O, e.g. with `Key` and the parsing helpers of this crate.
+///
+/// In raw mode, typed keys are passed on one by one, without echoing them.
+/// Dropping the guard restores the terminal mode from before, also when
+/// unwinding from a panic.
+///
+/// ```no_run
+/// use shli::RawModeGuard;
+///
+/// let guard = RawModeGuard::new().expect("stdout is no terminal");
+/// // Read keys …
+/// drop(guard);
+/// ```
+pub struct RawModeGuard(RawMode);
+
+impl RawModeGuard {
+    /// Put the terminal into raw mode
+    pub fn new() -> std::io::Result<RawModeGuard> {
+        Ok(RawModeGuard(RawMode::enter()?))
+    }
+
+    /// The raw mode held, which may be suspended temporarily
+    pub(crate) fn raw_mode(&self) -> &RawMode {
+        &self.0
+    }
+}
+
+/// Send SIGINT to the own process, as a cooked terminal does on Ctrl+C.
+///
+/// This does nothing on platforms without signals.
+pub(crate) fn raise_interrupt() {
+    #[cfg(unix)]
+    unsafe {
+        libc::raise(libc::SIGINT);
+    }
+}
+
+#[cfg(feature = "termion")]
+mod termion_backend {
+    use std::io::{stdout, Read, Stdout};
+    use termion::input::TermRead;
+    use termion::raw::{IntoRawMode, RawTerminal};
+
+    pub use termion::event::Key;
+    pub(crate) use termion::{clear, cursor, style};
+
+    /// Keeps the terminal in raw mode until dropped
+    pub struct RawMode(RawTerminal<Stdout>);
+
+    impl RawMode {
+        /// Put the terminal into raw mode
+        pub fn enter() -> std::io::Result<RawMode> {
+            Ok(RawMode(stdout().into_raw_mode()?))
+        }
+
+        /// Temporarily restore the previous terminal mode
+        pub fn suspend(&self) -> std::io::Result<()> {
+            self.0.suspend_raw_mode()
+        }
+
+        /// Enter raw mode again after `suspend`
+        pub fn activate(&self) -> std::io::Result<()> {
+            self.0.activate_raw_mode()
+        }
+    }
+
+    /// Reads a file descriptor like stdin directly, bypassing the buffer of
+    /// `std::io::Stdin`, so that `key_within` sees all input not read yet.
+    ///
+    /// A read returns everything available up to the size of the buffer, which
+    /// termion needs to tell an escape sequence from a single Esc key.
+    pub(crate) struct UnbufferedFd(pub(crate) libc::c_int);
+
+    impl Read for UnbufferedFd {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            loop {
+                let read = unsafe { libc::read(self.0, buf.as_mut_ptr().cast(), buf.len()) };
+                if read >= 0 {
+                    return Ok(read as usize);
+                }
+                let error = std::io::Error::last_os_error();
+                if error.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// The keys pressed by the user, read from stdin
+    pub fn keys() -> impl Iterator<Item = std::io::Result<Key>> {
+        UnbufferedFd(libc::STDIN_FILENO).keys()
+    }
+
+    /// Whether input from stdin is available within `timeout`, e.g. right away
+    /// after pasting
+    pub fn key_within(timeout: std::time::Duration) -> bool {
+        let mut stdin = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        unsafe { libc::poll(&mut stdin, 1, timeout) > 0 }
+    }
+
+    /// The width of the terminal in columns, 80 if unknown
+    pub fn width() -> u16 {
+        termion::terminal_size().map_or(80, |(width, _)| width)
+    }
+}
+
+#[cfg(all(feature = "crossterm", not(feature = "termion")))]
+mod crossterm_backend {
+    use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+    use crossterm::terminal;
+
+    /// A key pressed by the user, with the same variants as termion's `Key`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Key {
+        Backspace,
+        Left,
+        Right,
+        Up,
+        Down,
+        Home,
+        End,
+        PageUp,
+        PageDown,
+        BackTab,
+        Delete,
+        Insert,
+        F(u8),
+        Char(char),
+        Alt(char),
+        Ctrl(char),
+        Null,
+        Esc,
+    }
+
+    impl Key {
+        /// Convert a crossterm key event, if it has a counterpart
+        fn from_event(event: KeyEvent) -> Option<Key> {
+            let alt = event.modifiers.contains(KeyModifiers::ALT);
+            let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+            let key = match event.code {
+                KeyCode::Char(ch) if ctrl => Key::Ctrl(ch),
+                KeyCode::Char(ch) if alt => Key::Alt(ch),
+                KeyCode::Char(ch) => Key::Char(ch),
+                KeyCode::Enter if alt => Key::Alt('\r'),
+                KeyCode::Enter => Key::Char('\n'),
+                KeyCode::Tab => Key::Char('\t'),
+                KeyCode::Backspace if alt => Key::Alt('\u{7f}'),
+                KeyCode::Backspace => Key::Backspace,
+                KeyCode::Left => Key::Left,
+                KeyCode::Right => Key::Right,
+                KeyCode::Up => Key::Up,
+                KeyCode::Down => Key::Down,
+                KeyCode::Home => Key::Home,
+                KeyCode::End => Key::End,
+                KeyCode::PageUp => Key::PageUp,
+                KeyCode::PageDown => Key::PageDown,
+                KeyCode::BackTab => Key::BackTab,
+                KeyCode::Delete => Key::Delete,
+                KeyCode::Insert => Key::Insert,
+                KeyCode::F(n) => Key::F(n),
+                KeyCode::Null => Key::Null,
+                KeyCode::Esc => Key::Esc,
+                _ => return None,
+            };
+            Some(key)
+        }
+    }
+
+    /// Keeps the terminal in raw mode until dropped
+    pub struct RawMode(());
+
+    impl RawMode {
+        /// Put the terminal into raw mode
+        pub fn enter() -> std::io::Result<RawMode> {
+            terminal::enable_raw_mode()?;
+            Ok(RawMode(()))
+        }
+
+        /// Temporarily restore the previous terminal mode
+        pub fn suspend(&self) -> std::io::Result<()> {
+            terminal::disable_raw_mode()
+        }
+
+        /// Enter raw mode again after `suspend`
+        pub fn activate(&self) -> std::io::Result<()> {
+            terminal::enable_raw_mode()
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+
+    /// The keys pressed by the user
+    pub fn keys() -> impl Iterator<Item = std::io::Result<Key>> {
+        std::iter::from_fn(|| loop {
+            match event::read() {
+                Ok(Event::Key(event)) if event.kind != KeyEventKind::Release => {
+                    if let Some(key) = Key::from_event(event) {
+                        return Some(Ok(key));
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        })
+    }
+
+    /// Whether a key is available within `timeout`, e.g. right away after pasting
+    pub fn key_within(timeout: std::time::Duration) -> bool {
+        event::poll(timeout).unwrap_or(false)
+    }
+
+    /// The width of the terminal in columns, 80 if unknown
+    pub fn width() -> u16 {
+        terminal::size().map_or(80, |(width, _)| width)
+    }
+
+    /// ANSI escape sequences, written just like termion's
+    pub(crate) mod cursor {
+        use std::fmt;
+
+        pub struct Left(pub u16);
+
+        impl fmt::Display for Left {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[{}D", self.0)
+            }
+        }
+
+        pub struct Right(pub u16);
+
+        impl fmt::Display for Right {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[{}C", self.0)
+            }
+        }
+
+        pub struct Up(pub u16);
+
+        impl fmt::Display for Up {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[{}A", self.0)
+            }
+        }
+
+        pub struct Save;
+
+        impl fmt::Display for Save {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[s")
+            }
+        }
+
+        pub struct Restore;
+
+        impl fmt::Display for Restore {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[u")
+            }
+        }
+    }
+
+    pub(crate) mod clear {
+        use std::fmt;
+
+        pub struct UntilNewline;
+
+        impl fmt::Display for UntilNewline {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[K")
+            }
+        }
+
+        pub struct CurrentLine;
+
+        impl fmt::Display for CurrentLine {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[2K")
+            }
+        }
+
+        pub struct AfterCursor;
+
+        impl fmt::Display for AfterCursor {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[J")
+            }
+        }
+    }
+
+    pub(crate) mod style {
+        use std::fmt;
+
+        pub struct Faint;
+
+        impl fmt::Display for Faint {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[2m")
+            }
+        }
+
+        pub struct Reset;
+
+        impl fmt::Display for Reset {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "\x1b[m")
+            }
+        }
+    }
+}
@@ -1,4 +1,27 @@
-use crate::split::{split, EscapingState};
+use crate::completion::{
+    active_command, complete, complete_in_order, display_text, ArbitraryArgument, Argument,
+    Command, CompletionResult, Flag,
+};
+use crate::error::Error;
+use crate::generate::{format_help, generate_bash_completion, generate_zsh_completion};
+use crate::prompt::{
+    capitalize, check_arg_count, edit_externally, no_check, numeric_argument, read_confirmation,
+    transform_word, Action, BellStyle, CompletionState, CtrlDAction, EmptyLineBehavior,
+    InvalidLineBehavior, LineState, Prompt, Validation,
+};
+use crate::render::{ColumnRenderer, CompletionRenderer};
+use crate::split::{
+    join, quote, split, split_iter, split_passthrough, split_with, split_with_quotes,
+    strip_comment, try_split, CharClass, EscapingState, ParseError, Tokenizer,
+};
+use crate::terminal::Key;
+use crate::vi::{ViCommand, ViMode, ViState};
+use crate::width::visible_width;
+use crate::word::{next_word_boundary, prev_word_boundary};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
 
 #[test]
 fn parse_1() {
@@ -68,6 +91,48 @@ fn split_backslash_escape() {
     assert_eq!(components, normative_components);
 }
 
+#[test]
+fn split_iter_borrows_plain_components() {
+    for (cmdline, expected) in [
+        ("", vec![]),
+        ("  a  bc ", vec!["a", "bc"]),
+        (
+            "A \"'\" B  '\"' \\\\ C",
+            vec!["A", "'", "B", "\"", "\\", "C"],
+        ),
+        (
+            "x'' pre\"fix\"  a\\ b ä 'unclosed",
+            vec!["x", "prefix", "a b", "ä", "unclosed"],
+        ),
+        ("'' \\ab c\\d", vec!["", "ab", "cd"]),
+        ("foo \\", vec!["foo"]),
+        ("foo\\", vec!["foo"]),
+    ] {
+        let components: Vec<String> = split_iter(cmdline).map(Cow::into_owned).collect();
+        assert_eq!(components, expected, "{}", cmdline);
+        assert_eq!(split(cmdline), expected, "{}", cmdline);
+    }
+    let components: Vec<_> = split_iter("ls -l 'a b'").collect();
+    assert!(matches!(components[0], Cow::Borrowed("ls")));
+    assert!(matches!(components[1], Cow::Borrowed("-l")));
+    assert!(matches!(components[2], Cow::Owned(_)));
+}
+
+#[test]
+fn split_backtick_quoted() {
+    let cmdline = "run `a \"b\" 'c'` x`y z` \\`q";
+    assert_eq!(
+        split_with_quotes(cmdline, &['`']),
+        vec!["run", "a \"b\" 'c'", "xy z", "`q"]
+    );
+    // Without it, backticks are ordinary characters
+    assert_eq!(
+        split(cmdline),
+        vec!["run", "`a", "b", "c`", "x`y", "z`", "`q"]
+    );
+    assert_eq!(split_with_quotes("'a `b' \"`\"", &['`']), vec!["a `b", "`"]);
+}
+
 #[test]
 fn split_alltogether() {
     let cmdline = "A \"\'\" B  \'\"\' \\\\ C";
@@ -82,3 +147,1936 @@ fn split_alltogether() {
     ];
     assert_eq!(components, normative_components);
 }
+
+#[test]
+fn kill_to_end_and_yank_mid_line() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![]);
+    let mut line = String::from("print hello");
+    let mut right_line = String::from(" world");
+    prompt.kill_to_end(&mut right_line);
+    assert_eq!(right_line, "");
+    assert_eq!(prompt.kill_buffer, " world");
+
+    // Move the cursor left of "hello" and yank the killed text back in there
+    right_line = line.split_off(5);
+    prompt.yank(&mut line);
+    assert_eq!(format!("{}{}", line, right_line), "print world hello");
+}
+
+#[test]
+fn kill_word_backward() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![]);
+    let mut line = String::from("cat foo  bar  ");
+    prompt.kill_word_backward(&mut line, 1);
+    assert_eq!(line, "cat foo  ");
+    assert_eq!(prompt.kill_buffer, "bar  ");
+    prompt.kill_to_start(&mut line);
+    assert_eq!(line, "");
+    assert_eq!(prompt.kill_buffer, "cat foo  ");
+}
+
+#[test]
+fn kill_word_forward_mid_line() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![]);
+    let mut right_line = String::from(" foo bar");
+    prompt.kill_word_forward("cat", &mut right_line, 1);
+    assert_eq!(right_line, " bar");
+    assert_eq!(prompt.kill_buffer, " foo");
+
+    let mut right_line = String::from("\"a b\" c");
+    prompt.kill_word_forward("cat ", &mut right_line, 1);
+    assert_eq!(right_line, " c");
+}
+
+fn transform_word_of(
+    line: &str,
+    right_line: &str,
+    transform: fn(&str) -> String,
+) -> (String, String) {
+    let mut line = String::from(line);
+    let mut right_line = String::from(right_line);
+    transform_word(&mut line, &mut right_line, transform);
+    (line, right_line)
+}
+
+#[test]
+fn uppercase_word() {
+    let (line, right_line) = transform_word_of("set", " mIxEd Äpfel", str::to_uppercase);
+    assert_eq!(line, "set MIXED");
+    assert_eq!(right_line, " Äpfel");
+}
+
+#[test]
+fn lowercase_word() {
+    let (line, right_line) = transform_word_of("set ", "mIxEd Äpfel", str::to_lowercase);
+    assert_eq!(line, "set mixed");
+    assert_eq!(right_line, " Äpfel");
+}
+
+#[test]
+fn capitalize_word() {
+    let (line, right_line) = transform_word_of("set mIxEd", " äPFEL", capitalize);
+    assert_eq!(line, "set mIxEd Äpfel");
+    assert_eq!(right_line, "");
+}
+
+#[test]
+fn edit_externally_saved() {
+    let editors = vec![String::from("sed -i -e s/hello/world/ -e $a\\\\")];
+    let edited = edit_externally("print hello", &editors).unwrap();
+    assert_eq!(edited, Some(String::from("print world")));
+}
+
+#[test]
+fn edit_externally_failed() {
+    let editors = vec![String::from("false")];
+    assert_eq!(edit_externally("print hello", &editors).unwrap(), None);
+}
+
+#[test]
+fn edit_externally_fallback() {
+    let editors = vec![String::from("shli-no-such-editor"), String::from("true")];
+    let edited = edit_externally("print hello", &editors).unwrap();
+    assert_eq!(edited, Some(String::from("print hello")));
+}
+
+#[test]
+fn edit_externally_concurrently() {
+    let threads: Vec<_> = (0..8)
+        .map(|n| {
+            std::thread::spawn(move || {
+                let editors = vec![format!("sed -i -e s/x/{}/", n)];
+                edit_externally("print x", &editors).unwrap()
+            })
+        })
+        .collect();
+    for (n, thread) in threads.into_iter().enumerate() {
+        assert_eq!(thread.join().unwrap(), Some(format!("print {}", n)));
+    }
+}
+
+#[test]
+fn autosuggestion_from_history() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![]);
+    prompt.history = vec![
+        String::from("print hello"),
+        String::from("print world"),
+        String::from("exit"),
+    ];
+    assert_eq!(prompt.suggestion("print", ""), None);
+
+    prompt.autosuggest = true;
+    assert_eq!(prompt.suggestion("print", ""), Some(" world"));
+    assert_eq!(prompt.suggestion("print h", ""), Some("ello"));
+    assert_eq!(prompt.suggestion("exit", ""), None);
+    assert_eq!(prompt.suggestion("", ""), None);
+    // Only at the end of the command line
+    assert_eq!(prompt.suggestion("pr", "int"), None);
+}
+
+#[test]
+fn visible_width_ignores_escape_sequences() {
+    assert_eq!(visible_width("print"), 5);
+    assert_eq!(visible_width("\x1b[1;31mcat\x1b[0m --help"), 10);
+    assert_eq!(visible_width("\x1b]0;title\x07> "), 2);
+    assert_eq!(visible_width("\x1b]0;title\x1b\\> "), 2);
+    assert_eq!(visible_width("äöü"), 3);
+    assert_eq!(visible_width("日本\x1b[0m語"), 6);
+    assert_eq!(visible_width("\u{1f600} ok"), 5);
+}
+
+#[test]
+fn vi_mode_switching() {
+    let mut vi = ViMode::new();
+    assert_eq!(vi.state, ViState::Insert);
+    assert_eq!(vi.handle_key(Key::Char('h')), ViCommand::PassThrough);
+    assert_eq!(vi.handle_key(Key::Esc), ViCommand::MoveLeft);
+    assert_eq!(vi.state, ViState::Normal);
+    assert_eq!(vi.handle_key(Key::Char('h')), ViCommand::MoveLeft);
+    assert_eq!(vi.handle_key(Key::Char('\n')), ViCommand::PassThrough);
+    assert_eq!(vi.handle_key(Key::Char('a')), ViCommand::MoveRight);
+    assert_eq!(vi.state, ViState::Insert);
+    vi.handle_key(Key::Esc);
+    assert_eq!(vi.handle_key(Key::Char('i')), ViCommand::Nothing);
+    assert_eq!(vi.state, ViState::Insert);
+}
+
+#[test]
+fn vi_pending_operator() {
+    let mut vi = ViMode::new();
+    vi.handle_key(Key::Esc);
+    assert_eq!(vi.handle_key(Key::Char('d')), ViCommand::Nothing);
+    assert_eq!(vi.pending_operator, Some('d'));
+    assert_eq!(vi.handle_key(Key::Char('d')), ViCommand::KillLine);
+    assert_eq!(vi.pending_operator, None);
+    // An unknown motion cancels the operator
+    vi.handle_key(Key::Char('d'));
+    assert_eq!(vi.handle_key(Key::Char('q')), ViCommand::Nothing);
+    assert_eq!(vi.handle_key(Key::Char('x')), ViCommand::DeleteChar);
+}
+
+#[test]
+fn vi_commands() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![]);
+    let mut line = String::from("cat foo  bar");
+    let mut right_line = String::new();
+    prompt.apply_vi_command(ViCommand::StartOfLine, &mut line, &mut right_line);
+    assert_eq!((line.as_str(), right_line.as_str()), ("", "cat foo  bar"));
+    prompt.apply_vi_command(ViCommand::WordForward, &mut line, &mut right_line);
+    prompt.apply_vi_command(ViCommand::WordForward, &mut line, &mut right_line);
+    assert_eq!((line.as_str(), right_line.as_str()), ("cat foo  ", "bar"));
+    prompt.apply_vi_command(ViCommand::WordBackward, &mut line, &mut right_line);
+    assert_eq!((line.as_str(), right_line.as_str()), ("cat ", "foo  bar"));
+    prompt.apply_vi_command(ViCommand::DeleteChar, &mut line, &mut right_line);
+    assert_eq!((line.as_str(), right_line.as_str()), ("cat ", "oo  bar"));
+    prompt.apply_vi_command(ViCommand::EndOfLine, &mut line, &mut right_line);
+    assert_eq!((line.as_str(), right_line.as_str()), ("cat oo  ba", "r"));
+    prompt.apply_vi_command(ViCommand::KillLine, &mut line, &mut right_line);
+    assert_eq!((line.as_str(), right_line.as_str()), ("", ""));
+    assert_eq!(prompt.kill_buffer, "cat oo  bar");
+}
+
+#[test]
+fn numeric_argument_accumulates() {
+    assert_eq!(numeric_argument(None, Key::Alt('3')), Some(3));
+    assert_eq!(numeric_argument(Some(3), Key::Char('5')), Some(35));
+    assert_eq!(numeric_argument(Some(3), Key::Alt('5')), Some(35));
+    // Plain digits are only part of a numeric argument after Alt
+    assert_eq!(numeric_argument(None, Key::Char('5')), None);
+    assert_eq!(numeric_argument(None, Key::Alt('d')), None);
+    assert_eq!(numeric_argument(Some(3), Key::Ctrl('w')), None);
+}
+
+#[test]
+fn kill_words_repeatedly() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![]);
+    let mut line = String::from("cat foo bar baz");
+    prompt.kill_word_backward(&mut line, 2);
+    assert_eq!(line, "cat foo ");
+    assert_eq!(prompt.kill_buffer, "bar baz");
+
+    let mut right_line = String::from(" one two three");
+    prompt.kill_word_forward("cat", &mut right_line, 2);
+    assert_eq!(right_line, " three");
+    assert_eq!(prompt.kill_buffer, " one two");
+}
+
+/// Feed `keys` to `prompt` and return the result along with everything written
+fn read_keys(prompt: &mut Prompt, keys: &[Key]) -> (Result<Vec<String>, Error>, String) {
+    let mut output = vec![];
+    let result = prompt.read_from(keys.iter().map(|key| Ok(*key)), &mut output);
+    (result, String::from_utf8(output).unwrap())
+}
+
+/// The keys to type `text`
+fn typed(text: &str) -> Vec<Key> {
+    text.chars().map(Key::Char).collect()
+}
+
+#[test]
+fn masking_echo() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]).with_echo(|_| Some("*".to_string()));
+    let mut keys = typed("pw 'a b'");
+    keys.extend([Key::Left, Key::Left, Key::Char('c'), Key::Char('\n')]);
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["pw", "a cb"]);
+    assert!(!output.contains('a'));
+    assert!(output.contains("\r\x1b[2C*********\x1b[K\x1b[2D"));
+}
+
+#[test]
+fn read_typed_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let (result, output) = read_keys(&mut prompt, &typed("cat 'a b'\n"));
+    assert_eq!(result.unwrap(), vec!["cat", "a b"]);
+    assert!(output.starts_with("$ "));
+    assert!(output.contains("$ cat 'a b'"));
+    assert_eq!(prompt.history, vec!["cat 'a b'"]);
+}
+
+#[test]
+fn read_interrupted_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut keys = typed("cat");
+    keys.push(Key::Ctrl('c'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert!(matches!(result, Err(Error::CtrlC)));
+    assert!(prompt.history.is_empty());
+}
+
+#[test]
+fn keep_cancelled_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut keys = typed("cat a\\\nb c");
+    keys.extend([Key::Left, Key::Left, Key::Ctrl('c')]);
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert!(matches!(result, Err(Error::CtrlC)));
+    assert_eq!(prompt.cancelled_line(), Some("cat ab c"));
+
+    let (result, _) = read_keys(&mut prompt, &typed("ls\n"));
+    assert_eq!(result.unwrap(), vec!["ls"]);
+    assert_eq!(prompt.cancelled_line(), None);
+}
+
+#[test]
+fn read_with_kill_and_yank() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut keys = typed("print hello world");
+    keys.extend([Key::Left; 6]);
+    keys.push(Key::Ctrl('k'));
+    keys.extend([Key::Left; 6]);
+    keys.push(Key::Ctrl('y'));
+    keys.push(Key::Char('\n'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["print", "world", "hello"]);
+}
+
+#[test]
+fn read_recalled_history() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.history = vec![String::from("print one"), String::from("print two")];
+    let (result, _) = read_keys(&mut prompt, &[Key::Up, Key::Up, Key::Char('\n')]);
+    assert_eq!(result.unwrap(), vec!["print", "one"]);
+}
+
+#[test]
+fn read_plain_lines() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut input = std::io::Cursor::new("cat \"a b\"\r\n\nexit");
+    let mut output = vec![];
+    assert_eq!(
+        prompt
+            .read_plain_line(&mut input, &mut output, false, &no_check)
+            .unwrap(),
+        (
+            "cat \"a b\"".to_string(),
+            vec!["cat".to_string(), "a b".to_string()]
+        )
+    );
+    assert!(prompt
+        .read_plain_line(&mut input, &mut output, false, &no_check)
+        .unwrap()
+        .1
+        .is_empty());
+    assert_eq!(
+        prompt
+            .read_plain_line(&mut input, &mut output, false, &no_check)
+            .unwrap()
+            .1,
+        vec!["exit"]
+    );
+    assert!(matches!(
+        prompt.read_plain_line(&mut input, &mut output, false, &no_check),
+        Err(Error::CtrlD)
+    ));
+    assert_eq!(prompt.history, vec!["cat \"a b\"", "exit"]);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn read_keys_asynchronously() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    for key in typed("print hello\nexit") {
+        sender.send(Ok(key)).unwrap();
+    }
+    drop(sender);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut output = vec![];
+    let line = runtime.block_on(prompt.read_keys_async(&mut receiver, &mut output, None));
+    assert_eq!(line.unwrap(), vec!["print", "hello"]);
+    // The rest of the keys is still there for the next line
+    let line = runtime.block_on(prompt.read_keys_async(&mut receiver, &mut output, None));
+    assert_eq!(line.unwrap(), vec!["exit"]);
+    assert_eq!(prompt.history, vec!["print hello", "exit"]);
+}
+
+#[test]
+fn complete_arbitrary_argument_description() {
+    let commands = vec![
+        Command::new("cat").arg(Argument::ArbitraryArgument(ArbitraryArgument {
+            name: String::from("FILE"),
+            description: String::from("the input file"),
+        })),
+    ];
+    match complete("cat ", &commands) {
+        CompletionResult::Description(description) => {
+            assert!(description.contains("FILE"));
+            assert!(description.contains("the input file"));
+        }
+        result => panic!("Expected a description, got {:?}", result),
+    }
+}
+
+#[test]
+fn complete_flag_with_value() {
+    let mut output = Flag::new("--output");
+    output.arguments.push(ArbitraryArgument {
+        name: String::from("FILE"),
+        description: String::from("where to write to"),
+    });
+    assert_eq!(output.display(), "--output <FILE>");
+    let commands = vec![Command::new("cat")
+        .arg(Argument::Flag(output))
+        .arg("--help")];
+
+    let completions =
+        CompletionResult::PossibilityList(vec![String::from("--help"), String::from("--output")]);
+    assert_eq!(complete("cat --", &commands), completions);
+    assert_eq!(
+        display_text("cat --", "--output", &commands),
+        "--output <FILE>"
+    );
+    assert_eq!(display_text("cat --", "--help", &commands), "--help");
+}
+
+#[test]
+fn classify_characters() {
+    use CharClass::*;
+    let mut state = EscapingState::new();
+    let classes: Vec<CharClass> = "a \"b c\"\\ '\"'"
+        .chars()
+        .map(|ch| state.step_classify(ch))
+        .collect();
+    assert_eq!(
+        classes,
+        vec![
+            Literal,
+            Delimiter,
+            OpenQuote,
+            Literal,
+            Literal,
+            Literal,
+            CloseQuote,
+            EscapeStart,
+            Escaped,
+            OpenQuote,
+            Literal,
+            CloseQuote
+        ]
+    );
+}
+
+#[test]
+fn redraw_incrementally() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let text = "x".repeat(200);
+    let mut keys = typed(&text);
+    keys.extend([Key::Left; 100]);
+    keys.push(Key::Backspace);
+    keys.push(Key::Char('y'));
+    keys.push(Key::Char('\n'));
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(
+        result.unwrap(),
+        vec![format!("{}y{}", &text[..99], &text[100..])]
+    );
+    // Repainting the whole line for every key would write more than 20000 bytes.
+    assert!(output.len() < 1000, "{} bytes written", output.len());
+    assert!(output.starts_with(&format!("$ {}", text)));
+}
+
+#[test]
+fn complete_in_place() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![Command::new("cat").arg("--help")]);
+    let mut keys = typed("cat   \"a  b\"  --he\t");
+    keys.push(Key::Char('\n'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["cat", "a  b", "--help"]);
+    assert_eq!(prompt.history, vec!["cat   \"a  b\"  --help "]);
+}
+
+#[test]
+fn delete_word_in_place() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut keys = typed("foo   bar baz");
+    keys.push(Key::Alt('\u{7f}'));
+    keys.push(Key::Char('\n'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["foo", "bar"]);
+    assert_eq!(prompt.history, vec!["foo   bar "]);
+}
+
+#[test]
+fn delete_quoted_word() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut keys = typed("echo  'a  b'   \"c d\"  ");
+    keys.push(Key::Alt('\u{7f}'));
+    keys.push(Key::Char('\n'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["echo", "a  b"]);
+    assert_eq!(prompt.history, vec!["echo  'a  b'   "]);
+}
+
+#[test]
+fn resolve_subcommands() {
+    let commands = vec![Command::new("git")
+        .arg("--help")
+        .subcommand(Command::new("remote").subcommand(Command::new("add").arg("--fetch")))];
+    let tokens: Vec<String> = ["git", "remote", "add"].map(String::from).into();
+    assert_eq!(commands[0].resolve_path(&tokens).unwrap().name, "add");
+    assert!(commands[0].resolve_path(&tokens[1..]).is_none());
+    assert_eq!(
+        commands[0].completions(),
+        CompletionResult::PossibilityList(vec!["--help".to_string(), "remote".to_string()])
+    );
+    assert_eq!(
+        complete("git remote add --f", &commands),
+        CompletionResult::PossibilityList(vec!["--fetch".to_string()])
+    );
+}
+
+#[test]
+fn described_completions() {
+    let commands = vec![
+        Command::new("cat")
+            .arg(Flag::new("--help").description("Print help"))
+            .arg("--version"),
+        Command::new("exit"),
+    ];
+    assert_eq!(
+        complete("cat --", &commands),
+        CompletionResult::DescribedList(vec![
+            ("--help".to_string(), "Print help".to_string()),
+            ("--version".to_string(), String::new()),
+        ])
+    );
+    assert_eq!(
+        complete("", &commands),
+        CompletionResult::PossibilityList(vec!["cat".to_string(), "exit".to_string()])
+    );
+
+    let mut prompt = Prompt::new("$ ".to_string(), commands);
+    let mut keys = typed("cat --\t");
+    keys.push(Key::Char('\n'));
+    let (_, output) = read_keys(&mut prompt, &keys);
+    assert!(output.contains("\n\r   --help     Print help\n\r   --version  \n\r"));
+}
+
+#[test]
+fn complete_assigned_flag_values() {
+    let paths = |_: &[String]| {
+        vec![
+            "/etc/".to_string(),
+            "/usr/".to_string(),
+            "/etc.bak".to_string(),
+        ]
+    };
+    let commands = vec![Command::new("cc")
+        .arg(Flag::new("--output").value("FILE").complete_values(paths))
+        .arg(Flag::new("--define").value("NAME"))
+        .arg("--verbose")];
+    let list = |words: &[&str]| {
+        CompletionResult::PossibilityList(words.iter().map(|word| word.to_string()).collect())
+    };
+    assert_eq!(
+        complete("cc --output=/et", &commands),
+        list(&["--output=/etc.bak", "--output=/etc/"])
+    );
+    assert_eq!(complete("cc --output /u", &commands), list(&["/usr/"]));
+    assert_eq!(
+        complete("cc --define=", &commands),
+        CompletionResult::Description("NAME".to_string())
+    );
+    assert_eq!(complete("cc --verbose=", &commands), CompletionResult::None);
+    assert_eq!(complete("cc --unknown=", &commands), list(&[]));
+
+    let mut prompt = Prompt::new("$ ".to_string(), commands);
+    let (result, output) = read_keys(&mut prompt, &typed("cc --output=/u\tbin\n"));
+    assert!(output.contains("$ cc --output=/usr/\x1b[K"));
+    assert_eq!(result.unwrap(), vec!["cc", "--output=/usr/bin"]);
+}
+
+#[test]
+fn complete_after_flag_values() {
+    let commands = vec![Command::new("cc")
+        .arg(Flag::new("--output").value("FILE"))
+        .arg(Flag::new("--define").value("NAME").value("VALUE"))];
+    assert_eq!(
+        complete("cc --output ", &commands),
+        CompletionResult::Description("FILE".to_string())
+    );
+    assert_eq!(
+        complete("cc --define X ", &commands),
+        CompletionResult::Description("VALUE".to_string())
+    );
+    assert_eq!(
+        complete("cc --output main --", &commands),
+        CompletionResult::PossibilityList(vec!["--define".to_string(), "--output".to_string()])
+    );
+
+    // The command line is drawn again after the description, with the prompt
+    let mut prompt = Prompt::new("build$ ".to_string(), commands);
+    let mut keys = typed("cc --output  main.c");
+    keys.extend([Key::Left; 7]);
+    keys.extend([Key::Char('\t'), Key::Char('\n')]);
+    let (_, output) = read_keys(&mut prompt, &keys);
+    assert!(output.contains(" Parameter help: FILE\n\r\rbuild$ cc --output  main.c\x1b[K\x1b[7D"));
+}
+
+#[test]
+fn quote_round_trip() {
+    let words = ["a b", "it's", "\"quoted\"", "back\\slash", "tab\there"];
+    assert_eq!(split(&join(&words)), words);
+    assert_eq!(quote("plain"), "plain");
+}
+
+#[test]
+fn complete_with_spaces() {
+    let commands = vec![Command::new("open").subcommand(Command::new("a b"))];
+    let mut prompt = Prompt::new("$ ".to_string(), commands);
+    let mut keys = typed("open \"a\t");
+    keys.push(Key::Char('\n'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["open", "a b"]);
+    assert_eq!(prompt.history, vec!["open a\\ b "]);
+}
+
+#[test]
+fn complete_directories_partially() {
+    let commands = vec![Command::new("vim")
+        .subcommand(Command::new("src/"))
+        .subcommand(Command::new("Cargo.toml"))];
+    let mut prompt = Prompt::new("$ ".to_string(), commands);
+    let mut keys = typed("vim s\tlib.rs");
+    keys.push(Key::Char('\n'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["vim", "src/lib.rs"]);
+
+    let mut keys = typed("vim C\t");
+    keys.push(Key::Char('\n'));
+    read_keys(&mut prompt, &keys).0.unwrap();
+    assert_eq!(prompt.history[1], "vim Cargo.toml ");
+}
+
+#[test]
+fn line_callback() {
+    let lines = Rc::new(RefCell::new(vec![]));
+    let seen = Rc::clone(&lines);
+    let mut prompt = Prompt::new("$ ".to_string(), vec![])
+        .with_line_callback(move |line| seen.borrow_mut().push(line.to_string()));
+    read_keys(&mut prompt, &typed("ls  -l\n")).0.unwrap();
+    read_keys(&mut prompt, &typed("\n")).0.unwrap();
+    let mut keys = vec![Key::Up];
+    keys.extend(typed(" /\n"));
+    read_keys(&mut prompt, &keys).0.unwrap();
+    assert_eq!(*lines.borrow(), vec!["ls  -l", "ls  -l /"]);
+}
+
+#[test]
+fn custom_completion_key() {
+    let commands = vec![Command::new("print")];
+    let mut prompt = Prompt::new("$ ".to_string(), commands).with_completion_key(Key::Null);
+    let mut keys = typed("pr\t");
+    keys.push(Key::Null);
+    keys.push(Key::Char('\n'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["pr"]);
+
+    let mut keys = typed("pr");
+    keys.push(Key::Null);
+    keys.push(Key::Char('\n'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["print"]);
+}
+
+#[test]
+fn complete_within_word() {
+    let commands = vec![Command::new("print").arg("--all")];
+    let mut prompt = Prompt::new("$ ".to_string(), commands);
+    let mut keys = typed("prit --all");
+    keys.extend([Key::Left; 7]);
+    keys.push(Key::Char('\t'));
+    keys.extend(typed("x\n"));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["print", "x", "--all"]);
+}
+
+#[test]
+fn confirm_many_completions() {
+    let commands = vec![Command::new("cat"), Command::new("cd"), Command::new("cp")];
+    let mut prompt = Prompt::new("$ ".to_string(), commands);
+    prompt.completion_query_items = Some(2);
+    let (_, output) = read_keys(&mut prompt, &typed("c\tnx\n"));
+    assert!(output.contains("Display all 3 possibilities? (y/n)"));
+    assert!(!output.contains("Completions"));
+    assert_eq!(prompt.history, vec!["cx"]);
+
+    let (_, output) = read_keys(&mut prompt, &typed("c\ty\n"));
+    assert!(output.contains("Completions: [\"cat\", \"cd\", \"cp\"]"));
+    assert_eq!(prompt.history[1], "c");
+}
+
+#[test]
+fn restore_draft_after_history() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.history.push("echo hi there".to_string());
+    let mut keys = typed("echo hi");
+    keys.extend([Key::Left, Key::Up, Key::Up, Key::Down]);
+    keys.extend(typed("!\n"));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["echo", "hi!"]);
+}
+
+#[test]
+fn walk_history_up_and_down() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut walk = |keys: Vec<Key>| {
+        prompt.set_history(vec!["one".into(), "two".into(), "three".into()]);
+        read_keys(&mut prompt, &keys).0.unwrap()
+    };
+    // Down without browsing keeps the command line
+    let mut keys = typed("abc");
+    keys.extend([Key::Down, Key::Char('\n')]);
+    assert_eq!(walk(keys), vec!["abc"]);
+    // Up stops at the oldest entry, Down goes back to the empty draft
+    let mut keys = vec![Key::Up; 4];
+    keys.extend([Key::Down, Key::Char('\n')]);
+    assert_eq!(walk(keys), vec!["two"]);
+    let mut keys = vec![Key::Up; 3];
+    keys.extend([Key::Down; 4]);
+    keys.extend(typed("x\n"));
+    assert_eq!(walk(keys), vec!["x"]);
+    // An edited entry is detached, so Down keeps it
+    let mut keys = vec![Key::Up, Key::Up];
+    keys.extend(typed("!"));
+    keys.extend([Key::Down, Key::Char('\n')]);
+    assert_eq!(walk(keys), vec!["two!"]);
+    // and Up starts over from the newest entry
+    let keys = vec![Key::Up, Key::Up, Key::Ctrl('u'), Key::Up, Key::Char('\n')];
+    assert_eq!(walk(keys), vec!["three"]);
+    let mut keys = vec![Key::Up, Key::Up, Key::Up, Key::Backspace];
+    keys.extend([Key::Up, Key::Down, Key::Char('\n')]);
+    assert_eq!(walk(keys), vec!["on"]);
+}
+
+#[test]
+fn special_key_handler() {
+    let mut prompt =
+        Prompt::new("$ ".to_string(), vec![]).with_special_key_handler(|key| match key {
+            Key::F(1) => Some(Action::Print("Help!".to_string())),
+            Key::Insert => Some(Action::Insert("-v ".to_string())),
+            Key::PageUp => Some(Action::HistoryStart),
+            _ => None,
+        });
+    prompt.history = vec!["ls".to_string(), "pwd".to_string()];
+    let mut keys = typed("cat ");
+    keys.extend([Key::F(1), Key::F(2), Key::PageDown, Key::Insert]);
+    keys.extend(typed("a\n"));
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["cat", "-v", "a"]);
+    assert!(output.contains("\n\rHelp!\n\r$ cat "));
+
+    let (result, _) = read_keys(&mut prompt, &[Key::PageUp, Key::Char('\n')]);
+    assert_eq!(result.unwrap(), vec!["ls"]);
+}
+
+#[test]
+fn recall_history_with_cursor_within_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let long = "x".repeat(100);
+    let mut walk = |keys: Vec<Key>| {
+        prompt.set_history(vec!["echo hello world".into(), format!("{} y", long)]);
+        read_keys(&mut prompt, &keys)
+    };
+    let mut keys = typed("echo hello");
+    keys.extend([Key::Left, Key::Left, Key::Left, Key::Up, Key::Char('\n')]);
+    let (result, output) = walk(keys);
+    assert_eq!(result.unwrap(), vec!["echo", "hello", "world"]);
+    // The whole line is cleared before drawing the entry, with the cursor at its end
+    assert!(output.ends_with("\x1b[1D\r\x1b[J\r$ echo hello world\x1b[K"));
+
+    // A line wrapping into the next terminal line, with the cursor in the second one
+    let mut keys = typed(&long);
+    keys.extend([Key::Left, Key::Up, Key::Down, Key::Char('\n')]);
+    let (result, output) = walk(keys);
+    assert_eq!(result.unwrap(), vec![long.clone()]);
+    assert!(output.contains(&format!("\x1b[1D\x1b[1A\r\x1b[J\r$ {} y\x1b[K", long)));
+    assert!(output.ends_with(&format!("y\x1b[K\x1b[1A\r\x1b[J\r$ {}\x1b[K", long)));
+}
+
+#[test]
+fn disabled_history() {
+    let mut prompt = Prompt::builder()
+        .prompt_text("Password: ")
+        .history_enabled(false)
+        .build();
+    prompt.history = vec!["old".to_string()];
+    let mut keys = vec![Key::Up];
+    keys.extend(typed("secret\n"));
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["secret"]);
+    assert!(!output.contains("old"));
+    assert_eq!(prompt.history, vec!["old"]);
+}
+
+#[test]
+fn history_prefix_search() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    for entry in ["git status", "ls", "git log", "cargo test", "gitk"] {
+        prompt.history.push(entry.to_string());
+    }
+    let browse = |prompt: &mut Prompt, text: &str, moves: &[Key]| {
+        let mut keys = typed(text);
+        keys.extend(moves);
+        keys.push(Key::Char('\n'));
+        let result = read_keys(prompt, &keys).0.unwrap();
+        prompt.history.pop();
+        result
+    };
+    assert_eq!(browse(&mut prompt, "git ", &[Key::Up]), vec!["git", "log"]);
+    assert_eq!(
+        browse(&mut prompt, "git ", &[Key::Up, Key::Up]),
+        vec!["git", "status"]
+    );
+    assert_eq!(
+        browse(&mut prompt, "git ", &[Key::Up; 3]),
+        vec!["git", "status"]
+    );
+    assert_eq!(
+        browse(&mut prompt, "git ", &[Key::Up, Key::Up, Key::Down]),
+        vec!["git", "log"]
+    );
+    assert_eq!(
+        browse(&mut prompt, "", &[Key::Up, Key::Up]),
+        vec!["cargo", "test"]
+    );
+}
+
+#[test]
+fn split_at_custom_delimiters() {
+    let ascii = |ch| ch == ' ' || ch == '\t';
+    assert_eq!(
+        split_with("echo non\u{a0}breaking\t'a b'", ascii),
+        vec!["echo", "non\u{a0}breaking", "a b"]
+    );
+    assert_eq!(
+        split("echo non\u{a0}breaking"),
+        vec!["echo", "non", "breaking"]
+    );
+    assert_eq!(split_with("a,b\\,c", |ch| ch == ','), vec!["a", "b,c"]);
+}
+
+#[test]
+fn continue_after_backslash() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let (result, output) = read_keys(&mut prompt, &typed("echo a \\\nb\\\nc\n"));
+    assert_eq!(result.unwrap(), vec!["echo", "a", "bc"]);
+    assert!(output.contains("\n\r> \x1b[Kb"));
+    assert_eq!(prompt.history, vec!["echo a bc"]);
+    let (result, _) = read_keys(&mut prompt, &typed("echo a\\\\\n"));
+    assert_eq!(result.unwrap(), vec!["echo", "a\\"]);
+}
+
+#[test]
+fn reject_too_few_arguments() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let check = |words: &[String]| check_arg_count(words, 3, Some(3));
+    let keys = typed("cp a\n b\n").into_iter().map(Ok);
+    let mut output = vec![];
+    let (line, words) = prompt
+        .read_keys(keys, &mut output, None, &check, |_| false)
+        .unwrap();
+    assert_eq!(line, "cp a b");
+    assert_eq!(words, vec!["cp", "a", "b"]);
+    assert!(String::from_utf8(output)
+        .unwrap()
+        .contains("\n\rExpected 3 arguments, got 2\n\r$ cp a"));
+    assert_eq!(prompt.history, vec!["cp a b"]);
+    assert!(check_arg_count(&split("a b c d"), 1, None).is_ok());
+    assert!(check_arg_count(&split(""), 1, Some(2)).is_err());
+}
+
+#[test]
+fn render_columns() {
+    let words: Vec<String> = ["cat", "cd", "chmod", "cp", "cut"].map(String::from).into();
+    let result = CompletionResult::PossibilityList(words.clone());
+    assert_eq!(
+        String::from_utf8(ColumnRenderer.render(&result, 21)).unwrap(),
+        "\n\rcat    chmod  cut\n\rcd     cp"
+    );
+    assert_eq!(
+        String::from_utf8(ColumnRenderer.render(&result, 5)).unwrap(),
+        words
+            .iter()
+            .map(|word| format!("\n\r{}", word))
+            .collect::<String>()
+    );
+
+    let commands = words.iter().map(|word| Command::new(word)).collect();
+    let mut prompt =
+        Prompt::new("$ ".to_string(), commands).with_completion_renderer(ColumnRenderer);
+    let (_, output) = read_keys(&mut prompt, &typed("c\t\n"));
+    assert!(output.contains("cat"));
+    assert!(!output.contains("Completions"));
+}
+
+#[test]
+fn write_plain_prompt_to_output() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut output = vec![];
+    let check = |words: &[String]| check_arg_count(words, 2, None);
+    let line = prompt.read_plain_line(&b"ls\nls -l\n"[..], &mut output, true, &check);
+    assert_eq!(line.unwrap().1, vec!["ls", "-l"]);
+    assert_eq!(output, b"$ Expected at least 2 arguments, got 1\n$ ");
+}
+
+#[test]
+fn multiline_command() {
+    let mut prompt = Prompt::new("sql> ".to_string(), vec![]);
+    let mut keys = typed("SELECT *");
+    keys.push(Key::Alt('\r'));
+    keys.extend(typed("FROM t;\n"));
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["SELECT", "*", "FROM", "t;"]);
+    assert!(output.contains("\x1b[K\n\r> \x1b[KFROM t;"));
+    assert_eq!(prompt.history, vec!["SELECT *\nFROM t;"]);
+
+    // Recalled from the history, all lines are shown again
+    let (result, output) = read_keys(&mut prompt, &[Key::Up, Key::Char('\n')]);
+    assert_eq!(result.unwrap(), vec!["SELECT", "*", "FROM", "t;"]);
+    assert!(output.contains("\rsql> SELECT *\x1b[K\n\r> FROM t;"));
+
+    let mut prompt = prompt.with_submit_key(Key::Ctrl('s'));
+    let mut keys = typed("SELECT 1\n;");
+    keys.push(Key::Ctrl('s'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["SELECT", "1", ";"]);
+}
+
+#[test]
+fn recall_short_entry_over_long_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.history.push("ls".to_string());
+    let mut keys = typed("echo ünïcödé is long");
+    keys.extend([Key::Ctrl('u'), Key::Up, Key::Char('\n')]);
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["ls"]);
+    assert!(output.ends_with("\r\x1b[J\r$ ls\x1b[K"));
+    assert!(!output.contains("  "));
+}
+
+#[test]
+fn confirm_with_single_key() {
+    let confirm = |keys: &[Key], default| {
+        let mut output = vec![];
+        let keys = keys.iter().map(|key| Ok(*key));
+        let answer = read_confirmation(keys, &mut output, "Sure? ", default);
+        (answer, String::from_utf8(output).unwrap())
+    };
+    let (answer, output) = confirm(&[Key::Char('\n')], true);
+    assert!(answer.unwrap());
+    assert_eq!(output, "Sure? y\n\r");
+    assert!(!confirm(&[Key::Char('\n')], false).0.unwrap());
+    assert!(confirm(&[Key::Char('x'), Key::Char('Y')], false).0.unwrap());
+    assert!(!confirm(&[Key::Char('n')], true).0.unwrap());
+    assert!(matches!(
+        confirm(&[Key::Ctrl('c')], true).0,
+        Err(Error::CtrlC)
+    ));
+}
+
+#[test]
+fn operate_and_get_next() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    for entry in ["cd src", "make", "cd .."] {
+        prompt.history.push(entry.to_string());
+    }
+    let (result, _) = read_keys(&mut prompt, &[Key::Up, Key::Up, Key::Up, Key::Ctrl('o')]);
+    assert_eq!(result.unwrap(), vec!["cd", "src"]);
+    let (result, output) = read_keys(&mut prompt, &[Key::Ctrl('o')]);
+    assert_eq!(result.unwrap(), vec!["make"]);
+    assert!(output.starts_with("$ make"));
+    let (result, _) = read_keys(&mut prompt, &[Key::Char('\n')]);
+    assert_eq!(result.unwrap(), vec!["cd", ".."]);
+    // Used once only
+    let (result, _) = read_keys(&mut prompt, &[Key::Char('\n')]);
+    assert!(result.unwrap().is_empty());
+}
+
+#[test]
+fn complete_line_without_terminal() {
+    let commands = vec![
+        Command::new("git")
+            .subcommand(Command::new("commit").arg("--amend").arg("--all"))
+            .subcommand(Command::new("checkout")),
+        Command::new("grep"),
+    ];
+    let prompt = Prompt::new("$ ".to_string(), commands);
+    let list = |words: &[&str]| {
+        CompletionResult::PossibilityList(words.iter().map(|word| word.to_string()).collect())
+    };
+    assert_eq!(prompt.complete_line("g"), list(&["git", "grep"]));
+    assert_eq!(prompt.complete_line("git c"), list(&["checkout", "commit"]));
+    assert_eq!(
+        prompt.complete_line("git commit --a"),
+        list(&["--all", "--amend"])
+    );
+    assert_eq!(prompt.complete_line("git commit --am"), list(&["--amend"]));
+}
+
+#[test]
+fn no_flags_after_double_dash() {
+    let commands = vec![Command::new("mycmd")
+        .arg("--foo")
+        .arg(Flag::new("--out").value("FILE"))
+        .arg(Argument::dynamic(|_| {
+            vec!["--fake".to_string(), "file".to_string()]
+        }))];
+    let list = |words: &[&str]| {
+        CompletionResult::PossibilityList(words.iter().map(|word| word.to_string()).collect())
+    };
+    assert_eq!(complete("mycmd --f", &commands), list(&["--fake", "--foo"]));
+    assert_eq!(complete("mycmd -- --f", &commands), list(&["--fake"]));
+    assert_eq!(
+        complete("mycmd -- --out ", &commands),
+        list(&["--fake", "file"])
+    );
+    assert_eq!(complete("mycmd -- --out=", &commands), list(&[]));
+
+    let (own, passed) = split_passthrough("mycmd --foo -- --foo -- x");
+    assert_eq!(own, vec!["mycmd", "--foo"]);
+    assert_eq!(passed, vec!["--foo", "--", "x"]);
+    let (own, passed) = split_passthrough("mycmd \"--\" x");
+    assert_eq!(own, vec!["mycmd", "--", "x"]);
+    assert!(passed.is_empty());
+}
+
+#[test]
+fn sorted_unique_completions() {
+    let commands = vec![Command::new("remote")
+        .arg("show")
+        .arg("--verbose")
+        .subcommand(Command::new("show"))
+        .subcommand(Command::new("add"))];
+    let list = |words: &[&str]| {
+        CompletionResult::PossibilityList(words.iter().map(|word| word.to_string()).collect())
+    };
+    assert_eq!(
+        complete("remote ", &commands),
+        list(&["--verbose", "add", "show"])
+    );
+    assert_eq!(
+        complete_in_order("remote ", &commands),
+        list(&["show", "--verbose", "add"])
+    );
+
+    let prompt = Prompt::builder()
+        .commands(commands)
+        .sort_completions(false)
+        .build();
+    assert_eq!(
+        prompt.complete_line("remote "),
+        list(&["show", "--verbose", "add"])
+    );
+}
+
+#[test]
+fn completions_at_cursor() {
+    let commands = vec![
+        Command::new("git")
+            .subcommand(Command::new("commit").arg("--amend").arg("--all"))
+            .subcommand(Command::new("checkout")),
+        Command::new("grep"),
+    ];
+    let prompt = Prompt::new("$ ".to_string(), commands);
+    let list = |words: &[&str]| {
+        CompletionResult::PossibilityList(words.iter().map(|word| word.to_string()).collect())
+    };
+    // At the end
+    assert_eq!(prompt.completions_at("git co", 6), list(&["commit"]));
+    assert_eq!(prompt.completions_at("git co", 100), list(&["commit"]));
+    // Within a word
+    assert_eq!(
+        prompt.completions_at("git commit", 5),
+        list(&["checkout", "commit"])
+    );
+    assert_eq!(
+        prompt.completions_at("git commit --amend", 13),
+        list(&["--all", "--amend"])
+    );
+    // Within whitespace
+    assert_eq!(
+        prompt.completions_at("git  checkout", 4),
+        list(&["checkout", "commit"])
+    );
+    assert_eq!(prompt.completions_at("  git", 1), list(&["git", "grep"]));
+}
+
+#[test]
+fn generate_bash_script() {
+    let commands = vec![
+        Command::new("remote")
+            .arg("--verbose")
+            .subcommand(Command::new("add").arg("--fetch")),
+        Command::new("status"),
+    ];
+    let expected = r#"_vcs() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local path='vcs'
+    local word
+    for word in "${COMP_WORDS[@]:1:COMP_CWORD-1}"; do
+        case "$path $word" in
+            'vcs remote'|'vcs remote add'|'vcs status') path="$path $word" ;;
+        esac
+    done
+    case "$path" in
+        'vcs') COMPREPLY=($(compgen -W 'remote status' -- "$cur")) ;;
+        'vcs remote') COMPREPLY=($(compgen -W '--verbose add' -- "$cur")) ;;
+        'vcs remote add') COMPREPLY=($(compgen -W '--fetch' -- "$cur")) ;;
+        'vcs status') COMPREPLY=($(compgen -W '' -- "$cur")) ;;
+    esac
+}
+complete -o default -F _vcs 'vcs'
+"#;
+    assert_eq!(generate_bash_completion("vcs", &commands), expected);
+    assert!(generate_zsh_completion("vcs", &commands).ends_with(expected));
+}
+
+#[test]
+fn build_prompt_with_options() {
+    let mut prompt = Prompt::builder()
+        .prompt_text("sql> ")
+        .history(vec!["select 1;".to_string()])
+        .submit_key(Key::Ctrl('s'))
+        .build();
+    assert_eq!(prompt.continuation_prompt, "> ");
+    let mut keys = typed("a\nb");
+    keys.push(Key::Ctrl('s'));
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["a", "b"]);
+    assert!(output.starts_with("sql> "));
+    assert_eq!(prompt.history, vec!["select 1;", "a\nb"]);
+}
+
+#[test]
+fn remember_last_status() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![]);
+    assert_eq!(prompt.last_status(), None);
+    prompt.set_last_status(2);
+    let (result, _) = read_keys(&mut prompt, &typed("true\n"));
+    assert_eq!(result.unwrap(), vec!["true"]);
+    assert_eq!(prompt.last_status(), Some(2));
+}
+
+#[test]
+fn split_empty_quoted_arguments() {
+    assert_eq!(split("a '' b"), vec!["a", "", "b"]);
+    assert_eq!(split("a \"\" b"), vec!["a", "", "b"]);
+    assert_eq!(split("a ''"), vec!["a", ""]);
+    assert_eq!(split("a  b "), vec!["a", "b"]);
+    assert_eq!(split(&join(&["x", "", "y"])), vec!["x", "", "y"]);
+}
+
+#[test]
+fn split_concatenated_quotes() {
+    assert_eq!(split("'a'b\"c\""), vec!["abc"]);
+    assert_eq!(split("pre\"fix mid\"suf"), vec!["prefix midsuf"]);
+    assert_eq!(split("foo\"bar baz\"qux end"), vec!["foobar bazqux", "end"]);
+    assert_eq!(split("a''b \"\"'' x\\ y"), vec!["ab", "", "x y"]);
+}
+
+#[test]
+fn try_split_unfinished() {
+    assert_eq!(try_split("a \"b\" 'c'").unwrap(), vec!["a", "b", "c"]);
+    assert_eq!(
+        try_split("say \"it's"),
+        Err(ParseError::UnterminatedDoubleQuote { pos: 4 })
+    );
+    assert_eq!(
+        try_split("ä 'b' \\"),
+        Err(ParseError::TrailingBackslash { pos: 7 })
+    );
+    assert_eq!(
+        try_split("echo \"a\\\"b"),
+        Err(ParseError::UnterminatedDoubleQuote { pos: 5 })
+    );
+    assert_eq!(
+        try_split("echo 'a\\b"),
+        Err(ParseError::UnterminatedSingleQuote { pos: 5 })
+    );
+    assert_eq!(
+        ParseError::UnterminatedSingleQuote { pos: 3 }.to_string(),
+        "unterminated single quote at position 3"
+    );
+}
+
+#[test]
+fn set_prompt_between_reads() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![]);
+    prompt.history = vec!["a\nbc".to_string()];
+    prompt.set_prompt("\x1b[31m>\x1b[0m ");
+    let mut keys = typed("a");
+    keys.extend([Key::Alt('\r'), Key::Char('b'), Key::Up, Key::Char('\n')]);
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["a", "bc"]);
+    assert!(output.starts_with("\x1b[31m>\x1b[0m a"));
+    // Going back to the first line of the command line to replace it
+    assert!(output.contains("\x1b[1A\r\x1b[J"));
+}
+
+#[test]
+fn ring_bell_without_completions() {
+    let mut prompt = Prompt::new("> ".to_string(), vec![Command::new("print")]);
+    let (_, output) = read_keys(&mut prompt, &[Key::Char('x'), Key::Char('\t')]);
+    assert!(!output.contains('\x07'));
+
+    prompt.bell_style = BellStyle::Audible;
+    let (_, output) = read_keys(&mut prompt, &[Key::Char('x'), Key::Char('\t')]);
+    assert!(output.ends_with("x\x07"));
+    let (_, output) = read_keys(&mut prompt, &[Key::Char('p'), Key::Char('\t')]);
+    assert!(!output.contains('\x07'));
+
+    prompt.bell_style = BellStyle::Visible("\x1b[?5h\x1b[?5l".to_string());
+    let (_, output) = read_keys(&mut prompt, &[Key::Char('x'), Key::Char('\t')]);
+    assert!(output.ends_with("x\x1b[?5h\x1b[?5l"));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn run_typed_keys() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let (output, result) = prompt.run_keys(vec![Key::Char('a'), Key::Ctrl('c')]);
+    assert!(matches!(result, Err(Error::CtrlC)));
+    assert_eq!(output, b"$ a");
+}
+
+#[test]
+fn complete_prefix_before_listing() {
+    let commands = vec![Command::new("print"), Command::new("printf")];
+    let mut prompt = Prompt::new("> ".to_string(), commands);
+    prompt.complete_prefix_first = true;
+    let mut keys = typed("p\t");
+    let (_, output) = read_keys(&mut prompt, &keys);
+    assert!(output.ends_with("\r> print\x1b[K"));
+    assert!(!output.contains("Completions"));
+
+    keys.push(Key::Char('\t'));
+    let (_, output) = read_keys(&mut prompt, &keys);
+    assert!(output.ends_with("Completions: [\"print\", \"printf\"]\n\r\r> print\x1b[K"));
+
+    // Another key in between starts over
+    let mut keys = typed("print\t");
+    keys.extend([Key::Left, Key::Right, Key::Char('\t')]);
+    let (_, output) = read_keys(&mut prompt, &keys);
+    assert!(!output.contains("Completions"));
+
+    // Within a word, the rest of it is replaced too
+    let commands = vec![Command::new("git"), Command::new("github")];
+    let mut prompt = Prompt::new("> ".to_string(), commands);
+    prompt.complete_prefix_first = true;
+    let mut keys = typed("gt");
+    keys.extend([Key::Left, Key::Char('\t'), Key::Char('\n')]);
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["git"]);
+    assert!(output.contains("\r> git\x1b[K"));
+}
+
+#[test]
+fn replace_history() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.set_history(vec!["a".to_string(), "b".to_string()]);
+    let (result, _) = read_keys(&mut prompt, &[Key::Up, Key::Up, Key::Ctrl('o')]);
+    assert_eq!(result.unwrap(), vec!["a"]);
+    prompt.set_history(vec![String::new(), "x".to_string()]);
+    assert_eq!(prompt.history, vec!["x"]);
+    // The entry following "a" isn't recalled anymore
+    let (result, output) = read_keys(&mut prompt, &[Key::Up, Key::Char('\n')]);
+    assert_eq!(result.unwrap(), vec!["x"]);
+    assert!(output.starts_with("$ \r"));
+}
+
+#[test]
+fn complete_leaf_and_subcommand_only_commands() {
+    let commands = vec![
+        Command::new("exit"),
+        Command::new("remote")
+            .subcommand(Command::new("add"))
+            .subcommand(Command::new("remove")),
+    ];
+    assert_eq!(commands[0].completions(), CompletionResult::None);
+    assert_eq!(complete("exit ", &commands), CompletionResult::None);
+    assert_eq!(
+        complete("remote ", &commands),
+        CompletionResult::PossibilityList(vec!["add".to_string(), "remove".to_string()])
+    );
+    assert_eq!(complete("remote add ", &commands), CompletionResult::None);
+    assert_eq!(
+        complete("remote e", &commands),
+        CompletionResult::PossibilityList(vec![])
+    );
+}
+
+#[test]
+fn ctrl_d_within_command_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut keys = typed("abc");
+    keys.extend([Key::Left, Key::Left, Key::Ctrl('d'), Key::Char('\n')]);
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["ac"]);
+
+    prompt.ctrl_d_action = CtrlDAction::Ignore;
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["abc"]);
+
+    let (result, _) = read_keys(
+        &mut prompt,
+        &[Key::Char('a'), Key::Backspace, Key::Ctrl('d')],
+    );
+    assert!(matches!(result, Err(Error::CtrlD)));
+}
+
+#[test]
+fn strip_comments() {
+    assert_eq!(strip_comment("a \"b # c\" # d", "#"), "a \"b # c\" ");
+    assert_eq!(strip_comment("set x 1 // the x", "//"), "set x 1 ");
+    assert_eq!(strip_comment("a / b", "//"), "a / b");
+    assert_eq!(strip_comment("#all", "#"), "");
+    assert_eq!(strip_comment("curl a.org/#top #x", "#"), "curl a.org/#top ");
+    assert_eq!(strip_comment("echo 'a '#b", "#"), "echo 'a '#b");
+
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.comment_marker = Some("#".to_string());
+    let (result, _) = read_keys(&mut prompt, &typed("echo '#' hi # greet\n"));
+    assert_eq!(result.unwrap(), vec!["echo", "#", "hi"]);
+    assert_eq!(prompt.history, vec!["echo '#' hi # greet"]);
+}
+
+#[test]
+fn complete_dynamic_arguments() {
+    let seen = Rc::new(RefCell::new(vec![]));
+    let seen_by_closure = seen.clone();
+    let branches = Argument::dynamic(move |tokens| {
+        *seen_by_closure.borrow_mut() = tokens.to_vec();
+        vec!["main".to_string(), "feature".to_string()]
+    });
+    let checkout = Command::new("checkout").arg("--force").arg(branches);
+    let commands = vec![Command::new("git").subcommand(checkout)];
+    assert_eq!(
+        complete("git checkout --force f", &commands),
+        CompletionResult::PossibilityList(vec!["feature".to_string()])
+    );
+    assert_eq!(*seen.borrow(), vec!["git", "checkout", "--force"]);
+
+    let mut prompt = Prompt::new("$ ".to_string(), commands);
+    let (result, _) = read_keys(&mut prompt, &typed("git checkout m\t\n"));
+    assert_eq!(result.unwrap(), vec!["git", "checkout", "main"]);
+}
+
+#[test]
+fn survive_panicking_completion() {
+    let broken = || Command::new("ls").arg(Argument::dynamic(|_| panic!("broken completer")));
+    let mut prompt = Prompt::new("$ ".to_string(), vec![broken()]);
+    // Rung although `BellStyle::None` is the default
+    let (result, output) = read_keys(&mut prompt, &typed("ls x\ty\n"));
+    assert_eq!(result.unwrap(), vec!["ls", "xy"]);
+    assert!(output.contains("\x07\r$ ls x\x1b[K"));
+
+    let mut prompt = Prompt::builder()
+        .command(broken())
+        .catch_completion_panics(false)
+        .build();
+    let unwound = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        read_keys(&mut prompt, &typed("ls x\t"))
+    }));
+    assert!(unwound.is_err());
+}
+
+#[test]
+fn quoted_insert() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![Command::new("printf")]);
+    let mut keys = typed("printf 'a");
+    keys.extend([
+        Key::Ctrl('v'),
+        Key::Char('\t'),
+        Key::Ctrl('v'),
+        Key::Ctrl('a'),
+    ]);
+    keys.extend(typed("b'\n"));
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["printf", "a\t\x01b"]);
+    assert!(output.contains("\r\x1b[2Cprintf 'a^I^A"));
+    assert!(!output.contains('\t'));
+
+    let mut keys = typed("a");
+    keys.extend([Key::Ctrl('v'), Key::Char('\n'), Key::Ctrl('v'), Key::Left]);
+    keys.extend(typed("b\n"));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["a", "b"]);
+    assert_eq!(prompt.history[1], "a\nb");
+}
+
+#[test]
+fn word_boundaries() {
+    let text = "echo  \"a b\"  c\\ d ";
+    assert_eq!(prev_word_boundary(text, text.len()), 13);
+    assert_eq!(prev_word_boundary(text, 13), 6);
+    assert_eq!(prev_word_boundary(text, 9), 6);
+    assert_eq!(prev_word_boundary(text, 6), 0);
+    assert_eq!(prev_word_boundary("   ", 3), 0);
+    assert_eq!(next_word_boundary(text, 0), 4);
+    assert_eq!(next_word_boundary(text, 4), 11);
+    assert_eq!(next_word_boundary(text, 8), 11);
+    assert_eq!(next_word_boundary(text, 11), 17);
+    assert_eq!(next_word_boundary(text, 17), text.len());
+}
+
+#[test]
+fn submit_empty_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.history = vec!["ls -l".to_string()];
+    let (result, _) = read_keys(&mut prompt, &typed("\n"));
+    assert!(result.unwrap().is_empty());
+
+    prompt.empty_line = EmptyLineBehavior::RepeatLast;
+    let (result, output) = read_keys(&mut prompt, &typed("\n"));
+    assert_eq!(result.unwrap(), vec!["ls", "-l"]);
+    assert!(output.ends_with("$ ls -l\x1b[K"));
+    assert_eq!(prompt.history, vec!["ls -l"]);
+    let mut input: &[u8] = b"\n";
+    let result = prompt.read_plain_line(&mut input, &mut vec![], false, &no_check);
+    assert_eq!(result.unwrap().1, vec!["ls", "-l"]);
+    assert_eq!(prompt.history, vec!["ls -l"]);
+
+    prompt.empty_line = EmptyLineBehavior::Ignore;
+    let (result, output) = read_keys(&mut prompt, &typed("\n\npwd\n"));
+    assert_eq!(result.unwrap(), vec!["pwd"]);
+    assert!(output.starts_with("$ \n\r$ \n\r$ "));
+
+    let mut input: &[u8] = b"\nexit\n";
+    let mut output = vec![];
+    let result = prompt.read_plain_line(&mut input, &mut output, false, &no_check);
+    assert_eq!(result.unwrap().1, vec!["exit"]);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn print_above_interrupted_line() {
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut keys = typed("echo a\\\nhi");
+    keys.push(Key::Left);
+    for key in keys {
+        sender.send(Ok(key)).unwrap();
+    }
+    let mut output = vec![];
+    {
+        // Interrupt the read once all keys are processed
+        let read = std::pin::pin!(prompt.read_keys_async(&mut receiver, &mut output, None));
+        let mut context = Context::from_waker(Waker::noop());
+        assert!(matches!(read.poll(&mut context), Poll::Pending));
+    }
+    let mut output = vec![];
+    prompt
+        .print_above(&mut output, "job 1 done\njob 2 done")
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "\x1b[1A\r\x1b[Jjob 1 done\r\njob 2 done\r\n\r$ echo a\x1b[K\n\r> hi\x1b[K\x1b[1D"
+    );
+
+    for key in typed("!\n") {
+        sender.send(Ok(key)).unwrap();
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut output = vec![];
+    let line = runtime.block_on(prompt.read_keys_async(&mut receiver, &mut output, None));
+    assert_eq!(line.unwrap(), vec!["echo", "ah!i"]);
+}
+
+#[test]
+fn status_line_above_prompt() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]).with_status_line();
+    let mut output = vec![];
+    prompt.set_status_on(&mut output, "idle").unwrap();
+    assert!(output.is_empty());
+    let (result, output) = read_keys(&mut prompt, &typed("ls\n"));
+    assert_eq!(result.unwrap(), vec!["ls"]);
+    assert!(output.starts_with("idle\x1b[K\n\r$ ls"));
+
+    // A read interrupted without finishing leaves the status line on screen
+    let mut output = vec![];
+    prompt.begin_line(&mut output).unwrap();
+    prompt.set_status_on(&mut output, "busy").unwrap();
+    prompt.begin_line(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "idle\x1b[K\n\r$ \x1b[s\x1b[1A\rbusy\x1b[K\x1b[u\x1b[1A\r\x1b[Jbusy\x1b[K\n\r$ "
+    );
+}
+
+#[test]
+fn submit_with_ctrl_j() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut keys = typed("ls");
+    keys.push(Key::Ctrl('j'));
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["ls"]);
+    keys[2] = Key::Ctrl('m');
+    let (result, _) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["ls"]);
+}
+
+#[test]
+fn terminal_width_without_terminal() {
+    let prompt = Prompt::new("$ ".to_string(), vec![]);
+    assert!(prompt.terminal_width() > 0);
+}
+
+#[test]
+fn resolve_aliases() {
+    let commands = vec![Command::new("git").subcommand(
+        Command::new("commit")
+            .alias("co")
+            .arg("--amend")
+            .subcommand(Command::new("fixup").alias("f").arg("--quiet")),
+    )];
+    assert_eq!(
+        complete("git co --", &commands),
+        CompletionResult::PossibilityList(vec!["--amend".to_string()])
+    );
+    assert_eq!(
+        complete("git co f --", &commands),
+        CompletionResult::PossibilityList(vec!["--quiet".to_string()])
+    );
+    // Only the name is offered
+    assert_eq!(
+        complete("git c", &commands),
+        CompletionResult::PossibilityList(vec!["commit".to_string()])
+    );
+    assert!(generate_bash_completion("vcs", &commands).contains("'vcs git co f') COMPREPLY"));
+}
+
+#[test]
+fn hide_commands() {
+    let commands = vec![
+        Command::new("status"),
+        Command::new("stash")
+            .hidden()
+            .subcommand(Command::new("pop"))
+            .subcommand(Command::new("drop").hidden()),
+    ];
+    assert_eq!(
+        complete("", &commands),
+        CompletionResult::PossibilityList(vec!["status".to_string()])
+    );
+    assert_eq!(
+        complete("st", &commands),
+        CompletionResult::PossibilityList(vec!["status".to_string()])
+    );
+    assert_eq!(
+        complete("stash ", &commands),
+        CompletionResult::PossibilityList(vec!["pop".to_string()])
+    );
+    assert_eq!(
+        active_command(&split("stash drop"), &commands)
+            .unwrap()
+            .name,
+        "drop"
+    );
+}
+
+#[test]
+fn paste_long_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.autosuggest = true;
+    let pasted = format!("echo {}\n", "x".repeat(1019));
+    let mut typed_output = vec![];
+    let keys = typed(&pasted).into_iter().map(Ok);
+    prompt
+        .read_keys(keys, &mut typed_output, None, &no_check, |_| false)
+        .unwrap();
+    let mut pasted_output = vec![];
+    let keys = typed(&pasted).into_iter().map(Ok);
+    let (line, _) = prompt
+        .read_keys(keys, &mut pasted_output, None, &no_check, |_| true)
+        .unwrap();
+    assert_eq!(line.len(), 1024);
+    // Every typed character redraws the line, the pasted ones only once
+    assert!(typed_output.len() > 500_000);
+    assert!(pasted_output.len() < 1100);
+    assert!(String::from_utf8(pasted_output)
+        .unwrap()
+        .ends_with("x\x1b[K"));
+}
+
+#[test]
+fn trim_submitted_line() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.trim_on_submit = true;
+    let mut output = vec![];
+    let keys = typed("  ls  \n").into_iter().map(Ok);
+    let (line, words) = prompt
+        .read_keys(keys, &mut output, None, &no_check, |_| false)
+        .unwrap();
+    assert_eq!(line, "ls");
+    assert_eq!(words, vec!["ls"]);
+    let (result, _) = read_keys(&mut prompt, &typed("   \n"));
+    assert!(result.unwrap().is_empty());
+    let (result, _) = read_keys(&mut prompt, &typed(" touch a\\  \n"));
+    assert_eq!(result.unwrap(), vec!["touch", "a "]);
+    assert_eq!(prompt.history, vec!["ls", "touch a\\ "]);
+}
+
+#[test]
+fn validate_while_typing() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]).with_validator(|line| {
+        if line.trim().is_empty() {
+            Validation::Invalid("Type a command".to_string())
+        } else {
+            Validation::Valid
+        }
+    });
+    let (result, output) = read_keys(&mut prompt, &typed("\nls\n"));
+    assert_eq!(result.unwrap(), vec!["ls"]);
+    assert!(output.contains("\n\rType a command\n\r\x1b[31m$ \x1b[m\x1b[K"));
+    assert!(output.ends_with("\r$ ls\x1b[K"));
+    prompt.invalid_line = InvalidLineBehavior::Warn;
+    let (result, output) = read_keys(&mut prompt, &typed(" \n"));
+    assert!(result.unwrap().is_empty());
+    assert!(output.ends_with("\n\rType a command"));
+}
+
+#[test]
+fn backspace_grapheme_cluster() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    let mut keys = typed("ok \u{1f44d}\u{1f3fd}");
+    keys.push(Key::Backspace);
+    keys.push(Key::Char('\n'));
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["ok"]);
+    assert_eq!(prompt.history, vec!["ok "]);
+    assert!(output.ends_with("\r\x1b[2Cok \x1b[K"));
+}
+
+#[test]
+fn backspace_wide_char() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    for wide in ["\u{1f600}", "語"] {
+        let mut keys = typed(&format!("ok {}", wide));
+        keys.push(Key::Backspace);
+        keys.push(Key::Char('\n'));
+        let (result, output) = read_keys(&mut prompt, &keys);
+        assert_eq!(result.unwrap(), vec!["ok"]);
+        assert!(output.contains(&format!("{}\x1b[2D\x1b[K", wide)));
+    }
+}
+
+#[test]
+fn declare_command_tree() {
+    let declared = crate::commands! {
+        "git" {
+            flags: ["--version"]
+            "commit" { flags: ["--amend", "--message" = "MSG",] }
+            "remote" { "add" "remove" }
+        }
+        "exit"
+    };
+    let built = vec![
+        Command::new("git")
+            .arg("--version")
+            .subcommand(
+                Command::new("commit")
+                    .arg("--amend")
+                    .arg(Flag::new("--message").value("MSG")),
+            )
+            .subcommand(
+                Command::new("remote")
+                    .subcommand(Command::new("add"))
+                    .subcommand(Command::new("remove")),
+            ),
+        Command::new("exit"),
+    ];
+    assert_eq!(
+        generate_bash_completion("sh", &declared),
+        generate_bash_completion("sh", &built)
+    );
+    for line in [
+        "",
+        "git ",
+        "git commit --",
+        "git commit --message ",
+        "git remote r",
+    ] {
+        assert_eq!(complete(line, &declared), complete(line, &built));
+    }
+    assert_eq!(
+        complete("git commit --message ", &declared),
+        CompletionResult::Description("MSG".to_string())
+    );
+}
+
+#[test]
+fn reset_completion_state() {
+    let mut prompt = Prompt::new(
+        "$ ".to_string(),
+        vec![Command::new("print"), Command::new("pwd")],
+    );
+    prompt.complete_prefix_first = true;
+    prompt.completion_query_items = Some(1);
+    let mut state = LineState::default();
+    let mut output = vec![];
+    let mut press = |prompt: &mut Prompt, state: &mut LineState, key| {
+        prompt.process_key(state, Ok(key), &mut output, None)
+    };
+    press(&mut prompt, &mut state, Key::Char('p')).unwrap();
+    press(&mut prompt, &mut state, Key::Char('\t')).unwrap();
+    assert_eq!(state.completion, CompletionState::Completed);
+    press(&mut prompt, &mut state, Key::Left).unwrap();
+    assert_eq!(state.completion, CompletionState::Idle);
+    // Pressing the completion key again without another key in between
+    press(&mut prompt, &mut state, Key::Right).unwrap();
+    press(&mut prompt, &mut state, Key::Char('\t')).unwrap();
+    press(&mut prompt, &mut state, Key::Char('\t')).unwrap();
+    assert!(matches!(state.completion, CompletionState::Querying(_)));
+    // Answering the question
+    press(&mut prompt, &mut state, Key::Char('n')).unwrap();
+    assert_eq!(state.completion, CompletionState::Idle);
+    assert_eq!(state.line, "p");
+    press(&mut prompt, &mut state, Key::Char('\t')).unwrap();
+    assert!(press(&mut prompt, &mut state, Key::Ctrl('c')).is_err());
+    assert_eq!(state.completion, CompletionState::Idle);
+    press(&mut prompt, &mut state, Key::Char('\t')).unwrap();
+    assert!(press(&mut prompt, &mut state, Key::Char('\n')).unwrap());
+    assert_eq!(state.completion, CompletionState::Idle);
+}
+
+#[test]
+fn limit_line_length() {
+    let mut prompt = Prompt::builder()
+        .prompt_text("$ ")
+        .max_line_len(4)
+        .bell_style(BellStyle::Audible)
+        .build();
+    let mut keys = typed("ab\u{e4}");
+    keys.extend([Key::Left, Key::Char('c'), Key::Char('d')]);
+    // The rejected `d` leaves the cursor behind the `c`
+    keys.extend([Key::Backspace, Key::Char('x'), Key::Char('\n')]);
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["abx\u{e4}"]);
+    assert_eq!(output.matches('\x07').count(), 1);
+}
+
+#[test]
+fn command_help() {
+    let command = Command::new("commit")
+        .description("Record changes to the repository")
+        .arg(Flag::new("--amend").description("Replace the last commit"))
+        .arg(Flag::new("--message").value("MSG"))
+        .subcommand(Command::new("fixup").description("Commit a fixup"));
+    assert_eq!(
+        format_help(&command),
+        "Record changes to the repository\n\
+         \n\
+         Usage: commit [OPTIONS] [COMMAND]\n\
+         \n\
+         Commands:\n\
+         \x20 fixup            Commit a fixup\n\
+         \n\
+         Options:\n\
+         \x20 --amend          Replace the last commit\n\
+         \x20 --message <MSG>\n"
+    );
+}
+
+#[test]
+fn delay_suggestion() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.autosuggest = true;
+    prompt.suggestion_delay = Some(Duration::from_millis(150));
+    prompt.history.push("print hello".to_string());
+    let read = |prompt: &mut Prompt, key_within: fn(Duration) -> bool| {
+        let mut keys = typed("pr");
+        keys.extend([Key::Right, Key::Char('\n')]);
+        let mut output = vec![];
+        let keys = keys.into_iter().map(Ok);
+        let (line, _) = prompt
+            .read_keys(keys, &mut output, None, &no_check, key_within)
+            .unwrap();
+        prompt.history.pop();
+        (line, String::from_utf8(output).unwrap())
+    };
+    // Typing fast, the suggestion is neither shown nor accepted
+    let (line, output) = read(&mut prompt, |timeout| !timeout.is_zero());
+    assert_eq!(line, "pr");
+    assert!(!output.contains("int hello"));
+    // Pausing before Right
+    let (line, output) = read(&mut prompt, |_| false);
+    assert_eq!(line, "print hello");
+    assert!(output.contains("\r\x1b[2Cpr\x1b[2mint hello\x1b[m\x1b[K"));
+    // Keys from an iterator are typed without a pause
+    let mut keys = typed("pr");
+    keys.extend([Key::Right, Key::Char('\n')]);
+    let (result, output) = read_keys(&mut prompt, &keys);
+    assert_eq!(result.unwrap(), vec!["pr"]);
+    assert!(!output.contains("int hello"));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn delay_suggestion_asynchronously() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]);
+    prompt.autosuggest = true;
+    prompt.suggestion_delay = Some(Duration::from_millis(300));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let read = |prompt: &mut Prompt, pause: Duration| {
+        prompt.history.push("print hello".to_string());
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        for key in typed("pr") {
+            sender.send(Ok(key)).unwrap();
+        }
+        runtime.spawn(async move {
+            tokio::time::sleep(pause).await;
+            sender.send(Ok(Key::Right)).unwrap();
+            sender.send(Ok(Key::Char('\n'))).unwrap();
+        });
+        let mut output = vec![];
+        let line = runtime.block_on(prompt.read_keys_async(&mut receiver, &mut output, None));
+        prompt.history.clear();
+        (line.unwrap(), String::from_utf8(output).unwrap())
+    };
+    // Typing fast, the suggestion is neither shown nor accepted
+    let (line, output) = read(&mut prompt, Duration::from_millis(20));
+    assert_eq!(line, vec!["pr"]);
+    assert!(!output.contains("int hello"));
+    // Pausing before Right
+    let (line, output) = read(&mut prompt, Duration::from_millis(600));
+    assert_eq!(line, vec!["print", "hello"]);
+    assert!(output.contains("\r\x1b[2Cpr\x1b[2mint hello\x1b[m\x1b[K"));
+}
+
+#[test]
+fn expand_aliases() {
+    let mut prompt = Prompt::new("$ ".to_string(), vec![]).with_postprocessor(|mut words| {
+        if words.first().is_some_and(|word| word == "ll") {
+            words.splice(..1, ["ls".to_string(), "-l".to_string()]);
+        }
+        words
+    });
+    let (result, _) = read_keys(&mut prompt, &typed("ll -a\n"));
+    assert_eq!(result.unwrap(), vec!["ls", "-l", "-a"]);
+    let (result, _) = read_keys(&mut prompt, &[Key::Up, Key::Char('\n')]);
+    assert_eq!(result.unwrap(), vec!["ls", "-l", "-a"]);
+    let (result, _) = read_keys(&mut prompt, &[Key::Char('x'), Key::Ctrl('c')]);
+    assert!(matches!(result, Err(Error::CtrlC)));
+    assert_eq!(prompt.history, vec!["ll -a", "ll -a"]);
+}
+
+#[test]
+fn rank_completions_by_history() {
+    let git = Command::new("git")
+        .arg("--force")
+        .arg("--verbose")
+        .subcommand(Command::new("commit"))
+        .subcommand(Command::new("pull"))
+        .subcommand(Command::new("push"));
+    let mut prompt = Prompt::builder()
+        .command(git)
+        .command(Command::new("ls"))
+        .rank_completions(true)
+        .build();
+    prompt.history = [
+        "git pull",
+        "git push --verbose",
+        "ls push",
+        "git push",
+        "git commit",
+    ]
+    .map(String::from)
+    .to_vec();
+    let words = |words: &[&str]| {
+        CompletionResult::PossibilityList(words.iter().map(|word| word.to_string()).collect())
+    };
+    // Used twice, once and never
+    assert_eq!(prompt.complete_line("git p"), words(&["push", "pull"]));
+    // Once each, but `commit` most recently, and `pull` least recently
+    assert_eq!(
+        prompt.complete_line("git "),
+        words(&["push", "commit", "--verbose", "pull", "--force"])
+    );
+    assert_eq!(
+        prompt.complete_line("git --"),
+        words(&["--verbose", "--force"])
+    );
+    assert_eq!(prompt.complete_line(""), words(&["git", "ls"]));
+    prompt.rank_completions = false;
+    assert_eq!(
+        prompt.complete_line("git --"),
+        words(&["--force", "--verbose"])
+    );
+}
+
+#[test]
+fn tokenize_char_by_char() {
+    for cmdline in [
+        "echo hello world",
+        "  cat 'a b'  \"c\\\" d\" ",
+        "pre'fix mid'suf x\\ y ''",
+        "unterminated 'quote",
+        "",
+        "a \\",
+    ] {
+        let mut tokenizer = Tokenizer::new();
+        let mut words: Vec<String> = cmdline
+            .chars()
+            .filter_map(|ch| tokenizer.push(ch))
+            .collect();
+        words.extend(tokenizer.finish());
+        assert_eq!(words, split(cmdline), "{}", cmdline);
+    }
+    let mut tokenizer = Tokenizer::with_state(EscapingState::with_quotes(&['`']));
+    assert_eq!(tokenizer.push('`'), None);
+    assert_eq!(tokenizer.push(' '), None);
+    assert_eq!(tokenizer.current(), Some(" "));
+    assert!(tokenizer.state().whitespace_escaped());
+    assert_eq!(tokenizer.push('`'), None);
+    assert_eq!(tokenizer.push(' '), Some(" ".to_string()));
+    assert_eq!(tokenizer.finish(), None);
+
+    // A trailing backslash escapes nothing, so it doesn't start a component
+    let mut tokenizer = Tokenizer::new();
+    let words: Vec<String> = "a \\".chars().filter_map(|ch| tokenizer.push(ch)).collect();
+    assert_eq!(words, vec!["a"]);
+    assert_eq!(tokenizer.current(), None);
+    assert_eq!(tokenizer.finish(), None);
+}
+
+#[test]
+fn keep_prompt_while_editing() {
+    let prompt_text = "\x1b[1;32muser@host\x1b[0m:\x1b[1;34m~/projects/shli\x1b[0m $ ";
+    let mut prompt = Prompt::new(prompt_text.to_string(), vec![]);
+    prompt.autosuggest = true;
+    let (result, output) = read_keys(&mut prompt, &typed("echo hello world\n"));
+    assert_eq!(result.unwrap(), vec!["echo", "hello", "world"]);
+    // Only written before the first key, afterwards the cursor skips it
+    assert_eq!(output.matches(prompt_text).count(), 1);
+    assert!(output.ends_with("\r\x1b[28Cecho hello world\x1b[K"));
+    // Writing the 50 bytes of the prompt again for each of the 16 keys took 1050 bytes
+    assert_eq!(output.len(), 330);
+}
+
+#[cfg(feature = "termion")]
+#[test]
+fn read_escape_sequences_unbuffered() {
+    use crate::terminal::UnbufferedFd;
+    use termion::input::TermRead;
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let input = b"\x1b[A\x1bdx";
+    let written = unsafe { libc::write(fds[1], input.as_ptr().cast(), input.len()) };
+    assert_eq!(written, input.len() as isize);
+    unsafe { libc::close(fds[1]) };
+    let keys: Vec<Key> = UnbufferedFd(fds[0]).keys().map(Result::unwrap).collect();
+    unsafe { libc::close(fds[0]) };
+    assert_eq!(keys, vec![Key::Up, Key::Alt('d'), Key::Char('x')]);
+}
@@ -0,0 +1,123 @@
+use crate::terminal::Key::{self, Char};
+
+/// Whether vi mode currently inserts typed characters or interprets them as commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViState {
+    Insert,
+    Normal,
+}
+
+/// Edit command resulting from a key pressed in vi mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViCommand {
+    /// The key is handled as usual (insert mode or keys vi mode doesn't bind)
+    PassThrough,
+    /// The key was consumed without editing, e.g. the first `d` of `dd`
+    Nothing,
+    /// `h`
+    MoveLeft,
+    /// `l`
+    MoveRight,
+    /// `w`
+    WordForward,
+    /// `b`
+    WordBackward,
+    /// `0`
+    StartOfLine,
+    /// `$`
+    EndOfLine,
+    /// `x`
+    DeleteChar,
+    /// `D`
+    KillToEnd,
+    /// `dd`
+    KillLine,
+}
+
+/// State machine translating keys into vi commands.
+///
+/// It starts in insert mode, in which every key is passed through, except
+/// Esc, which switches to normal mode. There, keys are interpreted as commands
+/// until `i` or `a` return to insert mode.
+///
+/// ```
+/// use shli::vi::{ViCommand, ViMode, ViState};
+/// use shli::Key::{Char, Esc};
+///
+/// let mut vi = ViMode::new();
+/// assert_eq!(vi.handle_key(Char('x')), ViCommand::PassThrough);
+/// vi.handle_key(Esc);
+/// assert_eq!(vi.state, ViState::Normal);
+/// assert_eq!(vi.handle_key(Char('x')), ViCommand::DeleteChar);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ViMode {
+    pub state: ViState,
+    /// The operator waiting for its motion, e.g. after the first `d` of `dd`
+    pub pending_operator: Option<char>,
+}
+
+impl Default for ViMode {
+    fn default() -> ViMode {
+        ViMode::new()
+    }
+}
+
+impl ViMode {
+    pub fn new() -> ViMode {
+        ViMode {
+            state: ViState::Insert,
+            pending_operator: None,
+        }
+    }
+
+    /// Proceed with the pressed `key` and return the resulting command.
+    pub fn handle_key(&mut self, key: Key) -> ViCommand {
+        match self.state {
+            ViState::Insert => match key {
+                Key::Esc => {
+                    // Like vi, leaving insert mode moves onto the last inserted character.
+                    self.state = ViState::Normal;
+                    ViCommand::MoveLeft
+                }
+                _ => ViCommand::PassThrough,
+            },
+            ViState::Normal => {
+                if let Some(operator) = self.pending_operator.take() {
+                    return match (operator, key) {
+                        ('d', Char('d')) => ViCommand::KillLine,
+                        ('d', Char('$')) => ViCommand::KillToEnd,
+                        // Unknown motion, the operator is cancelled
+                        _ => ViCommand::Nothing,
+                    };
+                }
+                match key {
+                    Char('h') | Key::Left => ViCommand::MoveLeft,
+                    Char('l') | Key::Right => ViCommand::MoveRight,
+                    Char('w') => ViCommand::WordForward,
+                    Char('b') => ViCommand::WordBackward,
+                    Char('0') | Key::Home => ViCommand::StartOfLine,
+                    Char('$') | Key::End => ViCommand::EndOfLine,
+                    Char('x') => ViCommand::DeleteChar,
+                    Char('D') => ViCommand::KillToEnd,
+                    Char('d') => {
+                        self.pending_operator = Some('d');
+                        ViCommand::Nothing
+                    }
+                    Char('i') => {
+                        self.state = ViState::Insert;
+                        ViCommand::Nothing
+                    }
+                    Char('a') => {
+                        self.state = ViState::Insert;
+                        ViCommand::MoveRight
+                    }
+                    // Enter still submits the command line
+                    Char('\n') => ViCommand::PassThrough,
+                    Char(_) | Key::Esc => ViCommand::Nothing,
+                    _ => ViCommand::PassThrough,
+                }
+            }
+        }
+    }
+}
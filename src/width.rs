@@ -0,0 +1,51 @@
+use unicode_width::UnicodeWidthStr;
+
+/// Number of terminal columns `text` occupies, ignoring ANSI escape sequences.
+///
+/// Control sequences (`ESC [ … final byte`) and operating system commands
+/// (`ESC ] … BEL` or `ESC ] … ESC \`) do not take any space on the screen,
+/// so they are skipped. The remaining text is measured by its display width,
+/// so East Asian wide characters and emoji take two columns.
+///
+/// ```
+/// use shli::width::visible_width;
+///
+/// assert_eq!(visible_width("\x1b[32mprint\x1b[0m"), 5);
+/// assert_eq!(visible_width("日本"), 4);
+/// ```
+pub fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut plain_start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        if ch != '\x1b' {
+            continue;
+        }
+        width += text[plain_start..pos].width();
+        match chars.next() {
+            Some((_, '[')) => {
+                // Parameters and intermediate bytes, up to the final byte
+                for (_, ch) in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&ch) {
+                        break;
+                    }
+                }
+            }
+            Some((_, ']')) => {
+                while let Some((_, ch)) = chars.next() {
+                    if ch == '\x07' {
+                        break;
+                    }
+                    if ch == '\x1b' && matches!(chars.peek(), Some((_, '\\'))) {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Two-character escape sequence
+            _ => {}
+        }
+        plain_start = chars.peek().map_or(text.len(), |&(pos, _)| pos);
+    }
+    width + text[plain_start..].width()
+}
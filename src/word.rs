@@ -0,0 +1,72 @@
+//! Word boundaries for moving the cursor and deleting words.
+//!
+//! Words are delimited by unescaped whitespace, just as the components
+//! returned by `split`. So `'a b'` and `a\ b` are single words.
+
+use crate::split::{CharClass, EscapingState};
+
+/// The byte positions of the characters in `text`, each with whether it
+/// delimits words
+fn delimiters(text: &str) -> Vec<(usize, bool)> {
+    let mut state = EscapingState::new();
+    text.char_indices()
+        .map(|(pos, ch)| (pos, state.step_classify(ch) == CharClass::Delimiter))
+        .collect()
+}
+
+/// Byte position in `text` where the word before `cursor` starts.
+///
+/// If `cursor` is within a word, this is the start of that word. Delimiters
+/// directly left from `cursor` are skipped.
+///
+/// ```
+/// use shli::word::prev_word_boundary;
+///
+/// assert_eq!(prev_word_boundary("cat 'a b'  ", 11), 4);
+/// assert_eq!(prev_word_boundary("cat 'a b'", 2), 0);
+/// ```
+pub fn prev_word_boundary(text: &str, cursor: usize) -> usize {
+    let mut start = cursor.min(text.len());
+    let mut in_word = false;
+    for (pos, delimiter) in delimiters(text).into_iter().rev() {
+        if pos >= cursor {
+            continue;
+        }
+        if delimiter && in_word {
+            break;
+        }
+        in_word |= !delimiter;
+        start = pos;
+    }
+    start
+}
+
+/// Byte position in `text` where the word after `cursor` ends.
+///
+/// If `cursor` is within a word, this is the end of that word. Delimiters
+/// directly right from `cursor` are skipped.
+///
+/// ```
+/// use shli::word::next_word_boundary;
+///
+/// assert_eq!(next_word_boundary("cat  a\\ b c", 3), 9);
+/// assert_eq!(next_word_boundary("cat", 1), 3);
+/// ```
+pub fn next_word_boundary(text: &str, cursor: usize) -> usize {
+    delimiters(text)
+        .into_iter()
+        .skip_while(|(pos, _)| *pos < cursor)
+        .skip_while(|(_, delimiter)| *delimiter)
+        .find(|(_, delimiter)| *delimiter)
+        .map_or(text.len(), |(pos, _)| pos)
+}
+
+/// Byte position in `text` where the word after `cursor` starts (vi `w`)
+pub(crate) fn next_word_start(text: &str, cursor: usize) -> usize {
+    delimiters(text)
+        .into_iter()
+        .skip_while(|(pos, _)| *pos < cursor)
+        .skip_while(|(_, delimiter)| !*delimiter)
+        .find(|(_, delimiter)| !*delimiter)
+        .map_or(text.len(), |(pos, _)| pos)
+}